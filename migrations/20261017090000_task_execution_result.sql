@@ -0,0 +1,2 @@
+-- 任务执行结果
+ALTER TABLE task_executions ADD COLUMN result TEXT; -- 执行结果
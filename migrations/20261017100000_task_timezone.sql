@@ -0,0 +1,2 @@
+-- 任务时区
+ALTER TABLE tasks ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC'; -- IANA 时区，例如 Asia/Shanghai
@@ -0,0 +1,7 @@
+-- 任务错过运行的补偿策略
+ALTER TABLE tasks ADD COLUMN misfire_policy TEXT NOT NULL CHECK(misfire_policy IN ('skip', 'run_once', 'run_all')) DEFAULT 'skip'; -- 补偿策略
+ALTER TABLE tasks ADD COLUMN misfire_max_runs INTEGER NOT NULL DEFAULT 10; -- run_all 时最多补偿的次数
+
+-- 补偿执行标记
+ALTER TABLE task_executions ADD COLUMN is_catch_up BOOLEAN NOT NULL DEFAULT 0; -- 是否为补偿执行
+ALTER TABLE task_executions ADD COLUMN scheduled_at DATETIME; -- 补偿执行对应的原定运行时间
@@ -0,0 +1,15 @@
+-- 审计日志需在任务、用户删除后保留，重建 task_logs 去掉外键
+CREATE TABLE task_logs_new (
+    id TEXT PRIMARY KEY NOT NULL, -- 任务日志ID
+    task_id TEXT NOT NULL, -- 任务ID
+    user_id TEXT NOT NULL, -- 操作用户ID
+    action VARCHAR(50) NOT NULL, -- 操作
+    details TEXT, -- 详情（变更字段的 JSON diff）
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP -- 创建时间
+);
+INSERT INTO task_logs_new (id, task_id, user_id, action, details, created_at)
+    SELECT id, task_id, user_id, action, details, created_at FROM task_logs;
+DROP TABLE task_logs;
+ALTER TABLE task_logs_new RENAME TO task_logs;
+
+CREATE INDEX idx_task_logs_task_id ON task_logs(task_id, created_at);
@@ -0,0 +1,24 @@
+-- 程序运行记录
+CREATE TABLE IF NOT EXISTS program_runs (
+    id TEXT PRIMARY KEY NOT NULL, -- 运行ID
+    program_id TEXT NOT NULL REFERENCES programs(id) ON DELETE CASCADE, -- 程序ID
+    user_id TEXT NOT NULL, -- 运行用户ID
+    source_hash TEXT NOT NULL, -- 所运行源码的 SHA-256
+    source_code TEXT NOT NULL, -- 所运行的源码
+    input TEXT, -- 标准输入（截断）
+    status TEXT NOT NULL, -- 状态
+    exit_code INTEGER, -- 退出码
+    error TEXT, -- 错误信息
+    duration_ms INTEGER, -- 运行耗时
+    cpu_user_ms INTEGER, -- 用户态 CPU 时间
+    cpu_system_ms INTEGER, -- 内核态 CPU 时间
+    max_rss_kb INTEGER, -- 最大常驻内存
+    stdout TEXT, -- 标准输出（截断）
+    stderr TEXT, -- 标准错误（截断）
+    output_truncated BOOLEAN NOT NULL DEFAULT 0, -- 输入或输出是否被截断
+    artifact_path TEXT, -- 完整输入输出文件
+    started_at DATETIME DEFAULT CURRENT_TIMESTAMP, -- 开始时间
+    finished_at DATETIME -- 结束时间
+);
+
+CREATE INDEX idx_program_runs_program_id ON program_runs(program_id, started_at);
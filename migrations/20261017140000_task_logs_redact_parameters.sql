@@ -0,0 +1,4 @@
+-- 审计日志不再保存任务参数的取值，清除已记录的参数
+UPDATE task_logs
+SET details = json_set(details, '$.parameters', json('{"changed": true}'))
+WHERE json_valid(details) AND json_extract(details, '$.parameters') IS NOT NULL;
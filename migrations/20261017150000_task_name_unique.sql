@@ -0,0 +1,6 @@
+-- 同一用户的任务名称唯一，作为清单导入的匹配键；已有的重名任务在名称后追加序号
+UPDATE tasks
+SET name = name || ' (' || rowid || ')'
+WHERE rowid NOT IN (SELECT MIN(rowid) FROM tasks GROUP BY created_by, name);
+
+CREATE UNIQUE INDEX idx_tasks_created_by_name ON tasks(created_by, name);
@@ -62,7 +62,7 @@ pub async fn update_document(
         return Err(AppError::Auth("Insufficient permissions".to_string()));
     }
     let permission = permission.unwrap_or("read".to_string());
-    if permission == String::from("read") {
+    if permission == "read" {
         return Err(AppError::Auth("Insufficient permissions".to_string()));
     }
 
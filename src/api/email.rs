@@ -11,11 +11,13 @@ pub struct SendEmailRequest {
     pub body: String,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct SendEmailResponse {
     message: String,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct VerifyEmailResponse {
     message: String,
@@ -3,7 +3,7 @@ use crate::{
     db::DatabasePools,
     handlers,
     middleware::{monitor::track_metrics, require_auth},
-    services::{broadcast::MessageBroadcast, scheduler::Scheduler},
+    services::{broadcast::MessageBroadcast, program::ProgramService, scheduler::Scheduler},
     utils::{email::EmailService, python::PythonExecutor},
 };
 use axum::{
@@ -31,6 +31,7 @@ pub struct AppState {
     pub db: DatabasePools,
     pub email_service: EmailService,
     pub python_executor: PythonExecutor,
+    pub program_service: Arc<ProgramService>,
     pub broadcaster: Arc<MessageBroadcast>,
     pub scheduler: Arc<Mutex<Scheduler>>,
 }
@@ -46,7 +47,7 @@ pub fn init_router(state: Arc<AppState>) -> Router {
         .layer(cors)
         .layer(from_fn(require_auth))
         .layer(from_fn(track_metrics))
-        .layer(Extension(Arc::new(state)))
+        .layer(Extension(state))
         .fallback(handlers::handle_404)
 }
 
@@ -73,9 +74,29 @@ fn api_router() -> Router {
         // Task routes
         .route("/tasks", get(tasks::task_list))
         .route("/tasks", post(tasks::create_task))
+        .route("/tasks/cron/preview", get(tasks::cron_preview))
+        .route("/tasks/export", get(tasks::export_tasks))
+        .route("/tasks/import", post(tasks::import_tasks))
         .route("/tasks/:id", get(tasks::get_task))
         .route("/tasks/:id", put(tasks::update_task))
         .route("/tasks/:id", delete(tasks::delete_task))
+        .route("/tasks/:id/run", post(tasks::run_task))
+        .route("/tasks/:id/pause", post(tasks::pause_task))
+        .route("/tasks/:id/resume", post(tasks::resume_task))
+        .route("/tasks/:id/cancel", post(tasks::cancel_task))
+        .route("/tasks/:id/audit", get(tasks::list_audit_logs))
+        .route("/tasks/:id/executions", get(tasks::list_executions))
+        .route("/tasks/:id/executions/stats", get(tasks::execution_stats))
+        .route(
+            "/tasks/:id/executions/:execution_id",
+            get(tasks::get_execution),
+        )
+        .route("/tasks/:id/dependencies", get(tasks::list_dependencies))
+        .route("/tasks/:id/dependencies", post(tasks::add_dependency))
+        .route(
+            "/tasks/:id/dependencies/:prerequisite_id",
+            delete(tasks::remove_dependency),
+        )
         // Document routes
         .route("/documents", get(documents::list_documents))
         .route("/documents", post(documents::create_document))
@@ -94,7 +115,9 @@ fn api_router() -> Router {
         .route("/program/:id", put(program::update_program))
         .route("/program/:id", delete(program::delete_program))
         .route("/program/compile/:id", post(program::compile_program))
-        .route("/program/run/:id", post(program::run_program))
+        .route("/program/run/:id", get(program::run_program))
+        .route("/program/:id/runs", get(program::list_runs))
+        .route("/program/:id/runs/:run_id", get(program::get_run))
         // Websocket routes
         .route("/ws", get(websocket::ws_handler))
 }
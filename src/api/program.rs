@@ -1,8 +1,12 @@
-use axum::extract::{ws::Message, Path, Query, WebSocketUpgrade};
+use axum::extract::{
+    ws::{Message, WebSocket},
+    Path, Query, WebSocketUpgrade,
+};
 use axum::response::IntoResponse;
 use axum::{Extension, Json};
-use futures::{SinkExt, StreamExt};
+use futures::{stream::SplitSink, SinkExt, StreamExt};
 use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
 use uuid::Uuid;
 
 use crate::{
@@ -10,10 +14,11 @@ use crate::{
     error::AppError,
     middleware::auth::AuthUser,
     models::{
-        CreateProgramRequest, ListProgramQuery, ListProgramResponse, Program,
-        ProgramCompileResponse, ResponseResult, UpdateProgram,
+        CreateProgramRequest, ListProgramQuery, ListProgramResponse, ListProgramRunResponse,
+        ListProgramRunsQuery, Program, ProgramCompileResponse, ProgramEventKind, ProgramExecution,
+        ProgramRun, ProgramRunCommand, ResponseResult, UpdateProgram,
     },
-    services::program::{self, ProgramService},
+    services::program,
 };
 
 pub async fn list_programs(
@@ -37,12 +42,12 @@ pub async fn create_program(
     if req.name.is_empty() {
         return Err(AppError::BadRequest("Name is required".to_string()));
     }
-    let program = program::create_program(&state.db.sqlite, req).await?;
+    program::create_program(&state.db.sqlite, req).await?;
 
     Ok(Json(ResponseResult {
         code: 0,
         message: Some("Program created successfully".to_string()),
-        result: Some(program),
+        result: None,
     }))
 }
 
@@ -95,42 +100,132 @@ pub async fn delete_program(
     }))
 }
 
+/// 程序所有者和管理员可查看所有运行记录，其他用户只能查看自己发起的
+pub async fn list_runs(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(mut query): Query<ListProgramRunsQuery>,
+) -> Result<Json<ResponseResult<Vec<ListProgramRunResponse>>>, AppError> {
+    let program = program::get_program(&state.db.sqlite, id).await?;
+    if !auth.is_admin() && program.user_id != auth.user_id {
+        query.user_id = Some(auth.user_id);
+    }
+    let runs = program::list_runs(&state.db.sqlite, id, query).await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(runs),
+    }))
+}
+
+pub async fn get_run(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path((id, run_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ResponseResult<ProgramRun>>, AppError> {
+    let program = program::get_program(&state.db.sqlite, id).await?;
+    let run = program::get_run(&state.db.sqlite, id, run_id).await?;
+    if !auth.is_admin() && program.user_id != auth.user_id && run.user_id != auth.user_id {
+        return Err(AppError::Auth("Insufficient permissions".to_string()));
+    }
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(run),
+    }))
+}
+
 pub async fn compile_program(
-    _auth: AuthUser,
+    auth: AuthUser,
     Extension(state): Extension<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<ProgramCompileResponse>, AppError> {
-    let program_service =
-        ProgramService::new(state.db.sqlite.clone(), state.python_executor.clone());
-    let result = program_service.compile_program(id).await?;
+    owned_program(&state, &auth, id).await?;
+    let result = state.program_service.compile_program(id).await?;
     Ok(Json(result))
 }
 
+/// 运行程序并通过 WebSocket 逐行推送输出，推送 `Exit` 事件后关闭连接
 pub async fn run_program(
     ws: WebSocketUpgrade,
-    _auth: AuthUser,
+    auth: AuthUser,
     Extension(state): Extension<Arc<AppState>>,
     Path(id): Path<Uuid>,
-) -> impl IntoResponse {
-    let program_service =
-        ProgramService::new(state.db.sqlite.clone(), state.python_executor.clone());
-
-    ws.on_upgrade(move |socket| async move {
-        let (mut sender, _) = socket.split();
-
-        match program_service.run_program(id).await {
-            Ok(mut receiver) => {
-                while let Ok(update) = receiver.recv().await {
-                    if let Ok(msg) = serde_json::to_string(&update) {
-                        if sender.send(Message::Text(msg)).await.is_err() {
-                            break;
+) -> Result<impl IntoResponse, AppError> {
+    owned_program(&state, &auth, id).await?;
+    let program_service = state.program_service.clone();
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        let (mut sender, mut receiver) = socket.split();
+
+        match program_service.run_program(id, auth.user_id).await {
+            Ok(mut run) => {
+                // 客户端命令写入程序，无法解析的消息忽略；连接断开时结束程序。
+                // 命令通道写满时暂停读取，由 WebSocket 向客户端施加背压
+                let input = run.input.clone();
+                let commands = tokio::spawn(async move {
+                    while let Some(Ok(msg)) = receiver.next().await {
+                        match msg {
+                            Message::Text(text) => {
+                                if let Ok(command) =
+                                    serde_json::from_str::<ProgramRunCommand>(&text)
+                                {
+                                    if input.send(command).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            }
+                            Message::Close(_) => break,
+                            _ => {}
                         }
                     }
-                }
+                    let _ = input.send(ProgramRunCommand::Kill).await;
+                });
+
+                forward_run_events(&mut sender, &mut run.events).await;
+                // 发送失败提前结束时程序可能仍在运行
+                commands.abort();
+                let _ = run.input.send(ProgramRunCommand::Kill).await;
             }
             Err(e) => {
                 let _ = sender.send(Message::Text(format!("Error: {}", e))).await;
             }
         }
-    })
+    }))
+}
+
+/// 读取程序并检查是否为当前用户所有（管理员不限），只有所有者可以编译和运行
+async fn owned_program(state: &AppState, auth: &AuthUser, id: Uuid) -> Result<Program, AppError> {
+    let program = program::get_program(&state.db.sqlite, id).await?;
+    if !auth.is_admin() && program.user_id != auth.user_id {
+        return Err(AppError::Auth("Insufficient permissions".to_string()));
+    }
+    Ok(program)
+}
+
+/// 将本次运行的事件发送给客户端，程序退出后关闭连接
+async fn forward_run_events(
+    sender: &mut SplitSink<WebSocket, Message>,
+    events: &mut broadcast::Receiver<ProgramExecution>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(update) => {
+                let finished = update.kind == ProgramEventKind::Exit;
+                if let Ok(msg) = serde_json::to_string(&update) {
+                    if sender.send(Message::Text(msg)).await.is_err() {
+                        break;
+                    }
+                }
+                if finished {
+                    let _ = sender.close().await;
+                    break;
+                }
+            }
+            // 落后时丢弃部分事件，客户端可根据序号发现缺失
+            Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => break,
+        }
+    }
 }
@@ -1,23 +1,64 @@
 use axum::{
     extract::{Path, Query},
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
     Extension, Json,
 };
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
     error::AppError,
-    models::{CreateTaskRequest, ListTasksQuery, ResponseResult, ScheduledTask, UpdateTaskRequest},
-    services::task,
+    middleware::auth::AuthUser,
+    models::{
+        CreateTaskDependencyRequest, CreateTaskRequest, ExportManifestQuery, ImportManifestQuery,
+        ListAuditLogsQuery, ListExecutionsQuery, ListTasksQuery, ManifestAction, ManifestChange,
+        ResponseResult, ScheduledTask, TaskAuditAction, TaskAuditLog, TaskDependency,
+        TaskExecution, TaskExecutionStats, TaskType, UpdateTaskRequest,
+    },
+    services::{
+        dependency, execution,
+        executor::{parse_http_request, parse_program_id},
+        manifest::{self, ManifestFormat},
+        program, task,
+    },
+    utils::cron::{normalize_cron, parse_cron, parse_timezone, upcoming, validate_cron, CronError},
 };
 
 use super::AppState;
 
+#[derive(Debug, Deserialize)]
+pub struct CronPreviewQuery {
+    expression: String,
+    timezone: Option<String>,
+    count: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CronPreview {
+    expression: String, // 规范化后的表达式
+    timezone: String,
+    valid: bool,
+    next_runs: Vec<String>,
+    error: Option<CronError>,
+}
+
 pub async fn task_list(
+    auth: AuthUser,
     Extension(state): Extension<Arc<AppState>>,
-    Query(query): Query<ListTasksQuery>,
+    Query(mut query): Query<ListTasksQuery>,
 ) -> Result<Json<ResponseResult<Vec<ScheduledTask>>>, AppError> {
-    let tasks = task::list_tasks(&state.db.sqlite, query).await?;
+    // 普通用户只能看到自己创建的任务
+    if !auth.can_view_all_tasks() {
+        query.created_by = Some(auth.user_id);
+    }
+    let tasks = task::list_tasks(&state.db.sqlite, query)
+        .await?
+        .into_iter()
+        .map(|task| redact_task(&auth, task))
+        .collect();
 
     Ok(Json(ResponseResult {
         code: 0,
@@ -27,10 +68,35 @@ pub async fn task_list(
 }
 
 pub async fn create_task(
+    auth: AuthUser,
     Extension(state): Extension<Arc<AppState>>,
-    Json(req): Json<CreateTaskRequest>,
+    Json(mut req): Json<CreateTaskRequest>,
 ) -> Result<Json<ResponseResult<()>>, AppError> {
+    if !auth.is_admin() && !auth.is_user() {
+        return Err(AppError::Auth("Insufficient permissions".to_string()));
+    }
+    req.created_by = auth.user_id;
+    check_cron(req.cron_expression.as_deref())?;
+    if let Some(timezone) = &req.timezone {
+        parse_timezone(timezone)?;
+    }
+    check_misfire_max_runs(req.misfire_max_runs)?;
+    check_task_type(&auth, &req.task_type)?;
+    check_parameters(&state, &auth, &req.task_type, req.parameters.as_ref()).await?;
+    let id = req.id;
     task::create_task(&state.db.sqlite, req).await?;
+
+    let task = task::get_task(&state.db.sqlite, id).await?;
+    record_audit(
+        &state,
+        &auth,
+        id,
+        TaskAuditAction::Create,
+        None,
+        Some(&task),
+    )
+    .await?;
+    state.scheduler.lock().await.add_task(task).await?;
     Ok(Json(ResponseResult {
         code: 0,
         message: Some("Task created successfully".to_string()),
@@ -39,23 +105,48 @@ pub async fn create_task(
 }
 
 pub async fn get_task(
+    auth: AuthUser,
     Extension(state): Extension<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<ResponseResult<ScheduledTask>>, AppError> {
-    let task = task::get_task(&state.db.sqlite, id).await?;
+    let task = viewable_task(&state, &auth, id).await?;
     Ok(Json(ResponseResult {
         code: 0,
         message: None,
-        result: Some(task),
+        result: Some(redact_task(&auth, task)),
     }))
 }
 
 pub async fn update_task(
+    auth: AuthUser,
     Extension(state): Extension<Arc<AppState>>,
     Path(id): Path<Uuid>,
     Json(req): Json<UpdateTaskRequest>,
 ) -> Result<Json<ResponseResult<()>>, AppError> {
+    let before = manageable_task(&state, &auth, id).await?;
+    check_cron(req.cron_expression.as_deref())?;
+    if let Some(timezone) = &req.timezone {
+        parse_timezone(timezone)?;
+    }
+    check_misfire_max_runs(req.misfire_max_runs)?;
+    check_task_type(&auth, &before.task_type)?;
+    if let Some(parameters) = &req.parameters {
+        check_parameters(&state, &auth, &before.task_type, Some(parameters)).await?;
+    }
     task::update_task(&state.db.sqlite, id, req).await?;
+
+    // 重新调度（cron 变更、启用/停用、暂停）
+    let task = task::get_task(&state.db.sqlite, id).await?;
+    record_audit(
+        &state,
+        &auth,
+        id,
+        TaskAuditAction::Update,
+        Some(&before),
+        Some(&task),
+    )
+    .await?;
+    state.scheduler.lock().await.add_task(task).await?;
     Ok(Json(ResponseResult {
         code: 0,
         message: Some("Task updated successfully".to_string()),
@@ -64,13 +155,483 @@ pub async fn update_task(
 }
 
 pub async fn delete_task(
+    auth: AuthUser,
     Extension(state): Extension<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<ResponseResult<()>>, AppError> {
+    let before = manageable_task(&state, &auth, id).await?;
     task::delete_task(&state.db.sqlite, id).await?;
+    state.scheduler.lock().await.remove_task(id).await?;
+    record_audit(
+        &state,
+        &auth,
+        id,
+        TaskAuditAction::Delete,
+        Some(&before),
+        None,
+    )
+    .await?;
     Ok(Json(ResponseResult {
         code: 0,
         message: Some("Task deleted successfully".to_string()),
         result: None,
     }))
 }
+
+pub async fn list_dependencies(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ResponseResult<Vec<TaskDependency>>>, AppError> {
+    viewable_task(&state, &auth, id).await?;
+    let dependencies = dependency::list_dependencies(&state.db.sqlite, id).await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(dependencies),
+    }))
+}
+
+pub async fn add_dependency(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CreateTaskDependencyRequest>,
+) -> Result<Json<ResponseResult<TaskDependency>>, AppError> {
+    manageable_task(&state, &auth, id).await?;
+    viewable_task(&state, &auth, req.prerequisite_task_id).await?;
+    let dependency =
+        dependency::add_dependency(&state.db.sqlite, id, req.prerequisite_task_id).await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: Some("Dependency added successfully".to_string()),
+        result: Some(dependency),
+    }))
+}
+
+pub async fn remove_dependency(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path((id, prerequisite_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ResponseResult<()>>, AppError> {
+    manageable_task(&state, &auth, id).await?;
+    dependency::remove_dependency(&state.db.sqlite, id, prerequisite_id).await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: Some("Dependency removed successfully".to_string()),
+        result: None,
+    }))
+}
+
+pub async fn run_task(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ResponseResult<()>>, AppError> {
+    let task = manageable_task(&state, &auth, id).await?;
+    check_task_type(&auth, &task.task_type)?;
+    state.scheduler.lock().await.run_task(id).await?;
+    record_audit(&state, &auth, id, TaskAuditAction::Trigger, None, None).await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: Some("Task triggered successfully".to_string()),
+        result: None,
+    }))
+}
+
+pub async fn pause_task(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ResponseResult<()>>, AppError> {
+    let before = manageable_task(&state, &auth, id).await?;
+    state.scheduler.lock().await.pause_task(id).await?;
+    let after = task::get_task(&state.db.sqlite, id).await?;
+    record_audit(
+        &state,
+        &auth,
+        id,
+        TaskAuditAction::Pause,
+        Some(&before),
+        Some(&after),
+    )
+    .await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: Some("Task paused successfully".to_string()),
+        result: None,
+    }))
+}
+
+pub async fn resume_task(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ResponseResult<()>>, AppError> {
+    let before = manageable_task(&state, &auth, id).await?;
+    state.scheduler.lock().await.resume_task(id).await?;
+    let after = task::get_task(&state.db.sqlite, id).await?;
+    record_audit(
+        &state,
+        &auth,
+        id,
+        TaskAuditAction::Resume,
+        Some(&before),
+        Some(&after),
+    )
+    .await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: Some("Task resumed successfully".to_string()),
+        result: None,
+    }))
+}
+
+pub async fn cancel_task(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ResponseResult<Vec<Uuid>>>, AppError> {
+    let before = manageable_task(&state, &auth, id).await?;
+    let execution_ids = state.scheduler.lock().await.cancel_task(id).await?;
+    let after = task::get_task(&state.db.sqlite, id).await?;
+    record_audit(
+        &state,
+        &auth,
+        id,
+        TaskAuditAction::Cancel,
+        Some(&before),
+        Some(&after),
+    )
+    .await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: Some("Task canceled successfully".to_string()),
+        result: Some(execution_ids),
+    }))
+}
+
+pub async fn list_executions(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListExecutionsQuery>,
+) -> Result<Json<ResponseResult<Vec<TaskExecution>>>, AppError> {
+    let task = viewable_task(&state, &auth, id).await?;
+    let executions = execution::list_executions(&state.db.sqlite, id, query)
+        .await?
+        .into_iter()
+        .map(|execution| redact_execution(&auth, &task, execution))
+        .collect();
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(executions),
+    }))
+}
+
+pub async fn get_execution(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path((id, execution_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ResponseResult<TaskExecution>>, AppError> {
+    let task = viewable_task(&state, &auth, id).await?;
+    let execution = execution::get_execution(&state.db.sqlite, id, execution_id).await?;
+    let execution = redact_execution(&auth, &task, execution);
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(execution),
+    }))
+}
+
+pub async fn execution_stats(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListExecutionsQuery>,
+) -> Result<Json<ResponseResult<TaskExecutionStats>>, AppError> {
+    viewable_task(&state, &auth, id).await?;
+    let stats = execution::execution_stats(&state.db.sqlite, id, query.from, query.to).await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(stats),
+    }))
+}
+
+/// 任务的审计日志，已删除任务的审计日志仅管理员可查询
+pub async fn list_audit_logs(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListAuditLogsQuery>,
+) -> Result<Json<ResponseResult<Vec<TaskAuditLog>>>, AppError> {
+    if let Err(e) = viewable_task(&state, &auth, id).await {
+        if !(auth.is_admin() && matches!(e, AppError::NotFound(_))) {
+            return Err(e);
+        }
+    }
+    let logs = task::list_audit_logs(&state.db.sqlite, id, query).await?;
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(logs),
+    }))
+}
+
+pub async fn cron_preview(
+    Query(query): Query<CronPreviewQuery>,
+) -> Result<Json<ResponseResult<CronPreview>>, AppError> {
+    let timezone = query.timezone.unwrap_or_else(|| "UTC".to_string());
+    let tz = parse_timezone(&timezone)?;
+    let count = query.count.unwrap_or(5).clamp(1, 100);
+
+    let preview = match parse_cron(&query.expression) {
+        Ok(schedule) => CronPreview {
+            expression: normalize_cron(&query.expression),
+            timezone,
+            valid: true,
+            next_runs: upcoming(&schedule, tz, count)
+                .iter()
+                .map(|time| time.to_rfc3339())
+                .collect(),
+            error: None,
+        },
+        Err(error) => CronPreview {
+            expression: normalize_cron(&query.expression),
+            timezone,
+            valid: false,
+            next_runs: Vec::new(),
+            error: Some(error),
+        },
+    };
+
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: None,
+        result: Some(preview),
+    }))
+}
+
+/// 导出任务清单（JSON 或 YAML），只包含当前用户可查看的任务，不能管理的任务不含参数
+pub async fn export_tasks(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<ExportManifestQuery>,
+) -> Result<Response, AppError> {
+    let format = ManifestFormat::parse(query.format.as_deref())?;
+    let names: Option<Vec<String>> = query.names.map(|names| {
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect()
+    });
+    let tasks: Vec<ScheduledTask> = task::list_all_tasks(&state.db.sqlite)
+        .await?
+        .into_iter()
+        .filter(|task| auth.can_view_task(task))
+        .map(|task| redact_task(&auth, task))
+        .collect();
+
+    let manifest = manifest::export_manifest(&state.db.sqlite, &tasks, names.as_deref()).await?;
+    let body = format.serialize(&manifest)?;
+    Ok(([(CONTENT_TYPE, format.content_type())], body).into_response())
+}
+
+/// 按任务名称导入当前用户创建的任务，`dry_run` 时只返回变更计划
+pub async fn import_tasks(
+    auth: AuthUser,
+    Extension(state): Extension<Arc<AppState>>,
+    Query(query): Query<ImportManifestQuery>,
+    body: String,
+) -> Result<Json<ResponseResult<Vec<ManifestChange>>>, AppError> {
+    if !auth.is_admin() && !auth.is_user() {
+        return Err(AppError::Auth("Insufficient permissions".to_string()));
+    }
+    let format = ManifestFormat::parse(query.format.as_deref())?;
+    let manifest = format.deserialize(&body)?;
+    for entry in &manifest.tasks {
+        check_cron(entry.cron_expression.as_deref())?;
+        if let Some(timezone) = &entry.timezone {
+            parse_timezone(timezone)?;
+        }
+        check_misfire_max_runs(entry.misfire_max_runs)?;
+        check_task_type(&auth, &entry.task_type)?;
+    }
+
+    // 清单只作用于当前用户创建的任务，按 (created_by, name) 匹配
+    let tasks: Vec<ScheduledTask> = task::list_all_tasks(&state.db.sqlite)
+        .await?
+        .into_iter()
+        .filter(|task| task.created_by == auth.user_id)
+        .collect();
+    // 未给出参数时沿用现有任务的参数，新建的任务同样需要通过校验
+    for entry in &manifest.tasks {
+        let parameters = entry.parameters.as_ref().or_else(|| {
+            tasks
+                .iter()
+                .find(|task| task.name == entry.name)
+                .and_then(|task| task.parameters.as_ref())
+        });
+        check_parameters(&state, &auth, &entry.task_type, parameters).await?;
+    }
+    let plan = manifest::plan_import(
+        &state.db.sqlite,
+        &tasks,
+        &manifest,
+        query.deactivate_missing.unwrap_or(false),
+    )
+    .await?;
+    if query.dry_run.unwrap_or(false) {
+        return Ok(Json(ResponseResult {
+            code: 0,
+            message: Some("Dry run, no changes applied".to_string()),
+            result: Some(plan),
+        }));
+    }
+
+    let applied =
+        manifest::apply_import(&state.db.sqlite, &tasks, &manifest, plan, auth.user_id).await?;
+    for change in &applied {
+        let Some(id) = change.task_id else {
+            continue;
+        };
+        let (action, before) = match change.action {
+            ManifestAction::Create => (TaskAuditAction::Create, None),
+            ManifestAction::Update | ManifestAction::Deactivate => (
+                TaskAuditAction::Update,
+                tasks.iter().find(|task| task.id == id),
+            ),
+            ManifestAction::Unchanged => continue,
+        };
+        let task = task::get_task(&state.db.sqlite, id).await?;
+        record_audit(&state, &auth, id, action, before, Some(&task)).await?;
+        state.scheduler.lock().await.add_task(task).await?;
+    }
+
+    Ok(Json(ResponseResult {
+        code: 0,
+        message: Some("Manifest imported successfully".to_string()),
+        result: Some(applied),
+    }))
+}
+
+fn check_cron(expression: Option<&str>) -> Result<(), AppError> {
+    match expression {
+        Some(expression) if !validate_cron(expression) => Err(AppError::Validation(format!(
+            "Invalid cron expression: {}",
+            expression
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn check_misfire_max_runs(max_runs: Option<i32>) -> Result<(), AppError> {
+    match max_runs {
+        Some(max_runs) if max_runs < 1 => Err(AppError::Validation(
+            "misfire_max_runs must be at least 1".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 备份、清理、通知任务只有管理员可以创建和修改
+fn check_task_type(auth: &AuthUser, task_type: &TaskType) -> Result<(), AppError> {
+    if task_type.requires_admin() && !auth.is_admin() {
+        return Err(AppError::Auth(format!(
+            "Only administrators can manage {} tasks",
+            task_type
+        )));
+    }
+    Ok(())
+}
+
+/// 校验内置任务类型的参数，程序任务必须引用当前用户自己的程序（管理员不限）
+async fn check_parameters(
+    state: &AppState,
+    auth: &AuthUser,
+    task_type: &TaskType,
+    parameters: Option<&Value>,
+) -> Result<(), AppError> {
+    match task_type {
+        TaskType::Program => {
+            let program_id = parse_program_id(parameters)?;
+            let program = program::get_program(&state.db.sqlite, program_id).await?;
+            if !auth.is_admin() && program.user_id != auth.user_id {
+                return Err(AppError::Auth("Insufficient permissions".to_string()));
+            }
+        }
+        TaskType::HttpRequest => {
+            parse_http_request(parameters)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 记录操作人和变更字段；更新未改变任何字段时不记录
+async fn record_audit(
+    state: &AppState,
+    auth: &AuthUser,
+    task_id: Uuid,
+    action: TaskAuditAction,
+    before: Option<&ScheduledTask>,
+    after: Option<&ScheduledTask>,
+) -> Result<(), AppError> {
+    let details = task::audit_diff(before, after);
+    if action == TaskAuditAction::Update && details.as_object().is_some_and(|d| d.is_empty()) {
+        return Ok(());
+    }
+    task::record_audit(&state.db.sqlite, task_id, auth.user_id, action, details).await
+}
+
+/// 参数中可能包含请求头、令牌等凭据，只对能管理任务的用户返回
+fn redact_task(auth: &AuthUser, mut task: ScheduledTask) -> ScheduledTask {
+    if !auth.can_manage_task(&task) {
+        task.parameters = None;
+    }
+    task
+}
+
+/// 执行参数、结果和错误信息可能包含凭据、响应内容或程序输出，只对能管理任务的用户返回
+fn redact_execution(
+    auth: &AuthUser,
+    task: &ScheduledTask,
+    mut execution: TaskExecution,
+) -> TaskExecution {
+    if !auth.can_manage_task(task) {
+        execution.parameters = None;
+        execution.result = None;
+        execution.error_message = None;
+    }
+    execution
+}
+
+/// 读取任务并检查查看权限
+async fn viewable_task(
+    state: &AppState,
+    auth: &AuthUser,
+    id: Uuid,
+) -> Result<ScheduledTask, AppError> {
+    let task = task::get_task(&state.db.sqlite, id).await?;
+    if !auth.can_view_task(&task) {
+        return Err(AppError::Auth("Insufficient permissions".to_string()));
+    }
+    Ok(task)
+}
+
+/// 读取任务并检查修改权限
+async fn manageable_task(
+    state: &AppState,
+    auth: &AuthUser,
+    id: Uuid,
+) -> Result<ScheduledTask, AppError> {
+    let task = task::get_task(&state.db.sqlite, id).await?;
+    if !auth.can_manage_task(&task) {
+        return Err(AppError::Auth("Insufficient permissions".to_string()));
+    }
+    Ok(task)
+}
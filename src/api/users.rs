@@ -161,9 +161,9 @@ pub async fn upload_user_avatar(
     }
 
     // 处理上传的文件
-    while let Some(field) = multipart.next_field().await? {
+    if let Some(field) = multipart.next_field().await? {
         let file_name = field.file_name().unwrap_or("avatar.png").to_string();
-        let file_path = format!("./uploads/{}", file_name);
+        let file_path = format!("{}/{}", state.config.uploads_dir, file_name);
 
         // 保存文件
         let data = field.bytes().await?;
@@ -35,12 +35,13 @@ async fn handle_socket(socket: WebSocket, broadcast: Arc<MessageBroadcast>, auth
 
     let subscriptions = Arc::new(Mutex::new(HashSet::new()));
     let subscriptions_clone = subscriptions.clone();
+    let subscriber = auth.clone();
 
     // 创建一个任务来处理广播消息
     let send_task = tokio::spawn(async move {
         while let Ok(msg) = broadcast_receiver.recv().await {
-            if should_send(&subscriptions_clone.lock().unwrap(), &msg) {
-                let msg = serde_json::to_string(&msg).unwrap();
+            if should_send(&subscriptions_clone.lock().unwrap(), &msg, &subscriber) {
+                let msg = serde_json::to_string(&redact(msg, &subscriber)).unwrap();
                 if let Err(e) = sender.send(Message::Text(msg)).await {
                     eprintln!("Error sending message: {}", e);
                     break;
@@ -58,21 +59,42 @@ async fn handle_socket(socket: WebSocket, broadcast: Arc<MessageBroadcast>, auth
     });
 
     // 等待两个任务完成
-    let _ = tokio::select! {
+    tokio::select! {
         _ = send_task => {},
         _ = recv_task => {},
-    };
+    }
 }
 
-fn should_send(subscriptions: &HashSet<MessageType>, msg: &SocketPushMessage) -> bool {
+fn should_send(
+    subscriptions: &HashSet<MessageType>,
+    msg: &SocketPushMessage,
+    auth: &AuthUser,
+) -> bool {
     match msg {
         SocketPushMessage::Document(_) => subscriptions.contains(&MessageType::Document),
-        SocketPushMessage::TaskProgress() => subscriptions.contains(&MessageType::Task),
+        // 只推送订阅者有权查看的任务
+        SocketPushMessage::TaskProgress(progress) => {
+            subscriptions.contains(&MessageType::Task)
+                && auth.can_view_tasks_of(progress.created_by)
+        }
         SocketPushMessage::Notification() => subscriptions.contains(&MessageType::Notification),
         SocketPushMessage::SystemMetrics() => subscriptions.contains(&MessageType::System),
     }
 }
 
+/// 任务结束时的说明可能是错误信息，只推送给能管理该任务的订阅者
+fn redact(msg: SocketPushMessage, auth: &AuthUser) -> SocketPushMessage {
+    match msg {
+        SocketPushMessage::TaskProgress(mut progress)
+            if !auth.can_manage_tasks_of(progress.created_by) =>
+        {
+            progress.message = None;
+            SocketPushMessage::TaskProgress(progress)
+        }
+        msg => msg,
+    }
+}
+
 fn handle_client_message(
     subscriptions: &mut HashSet<MessageType>,
     message: &Message,
@@ -81,12 +103,12 @@ fn handle_client_message(
     // 处理客户端发送的消息
     // 例如，解析消息类型和内容，更新订阅列表等
     if let Message::Text(text) = message {
-        if let Ok(cmd) = serde_json::from_str::<ClientCommand>(&text) {
+        if let Ok(cmd) = serde_json::from_str::<ClientCommand>(text) {
             match cmd {
-                ClientCommand::Subscribe(msg_type) => {
+                ClientCommand::Subscribe { msg_type } => {
                     subscriptions.insert(msg_type);
                 }
-                ClientCommand::Unsubscribe(msg_type) => {
+                ClientCommand::Unsubscribe { msg_type } => {
                     subscriptions.remove(&msg_type);
                 }
                 ClientCommand::SubscribeWithFilter { msg_type, filter } => {
@@ -101,3 +123,61 @@ fn handle_client_message(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{TaskProgressMessage, TaskStatus};
+    use uuid::Uuid;
+
+    fn progress(created_by: Uuid) -> SocketPushMessage {
+        SocketPushMessage::TaskProgress(TaskProgressMessage {
+            task_id: Uuid::new_v4(),
+            task_name: "backup".to_string(),
+            created_by,
+            execution_id: Uuid::new_v4(),
+            attempt_number: 1,
+            percent: None,
+            message: Some("connection refused: https://internal".to_string()),
+            status: TaskStatus::Failed,
+        })
+    }
+
+    fn auth(role: &str) -> AuthUser {
+        AuthUser {
+            user_id: Uuid::new_v4(),
+            role: role.to_string(),
+        }
+    }
+
+    fn message(msg: SocketPushMessage) -> Option<String> {
+        match msg {
+            SocketPushMessage::TaskProgress(progress) => progress.message,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn task_progress_goes_to_owner_admin_and_guests() {
+        let subscriptions = HashSet::from([MessageType::Task]);
+        let (owner, other, guest, admin) =
+            (auth("user"), auth("user"), auth("guest"), auth("admin"));
+        let msg = progress(owner.user_id);
+
+        assert!(should_send(&subscriptions, &msg, &owner));
+        assert!(should_send(&subscriptions, &msg, &admin));
+        assert!(should_send(&subscriptions, &msg, &guest));
+        assert!(!should_send(&subscriptions, &msg, &other));
+        assert!(!should_send(&HashSet::new(), &msg, &owner));
+    }
+
+    #[test]
+    fn task_progress_message_is_hidden_from_non_managers() {
+        let owner = auth("user");
+        let msg = progress(owner.user_id);
+
+        assert_eq!(message(redact(msg.clone(), &auth("guest"))), None);
+        assert!(message(redact(msg.clone(), &owner)).is_some());
+        assert!(message(redact(msg, &auth("admin"))).is_some());
+    }
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+use tracing::info;
+use work_designer_server::{services::executor::restore_backup, AppError, Config};
+
+/// 从 `DataBackup` 任务生成的备份恢复数据库
+///
+/// 用法: `restore <备份文件>`，恢复前需停止服务。
+#[tokio::main]
+async fn main() -> Result<(), AppError> {
+    tracing_subscriber::fmt::init();
+
+    let artifact = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .ok_or_else(|| AppError::InvalidInput("Usage: restore <backup file>".to_string()))?;
+
+    let config = Config::new()?;
+    let database = restore_backup(&artifact, &config.database_url).await?;
+    info!("Database restored to {}", database.display());
+
+    Ok(())
+}
@@ -1,9 +1,10 @@
 use config::{Config as ConfigReader, Environment, File};
 use dotenv::dotenv;
 use serde::Deserialize;
+use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::AppError;
+use crate::{services::executor::DEFAULT_MAX_CONCURRENCY, utils::python::SandboxLimits, AppError};
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
@@ -18,12 +19,112 @@ pub struct Config {
     pub system_user_id: Uuid,
     #[serde(default = "default_system_email")]
     pub system_user_email: String,
+    #[serde(default = "default_metrics_port")]
+    pub metrics_port: u16,
+    #[serde(default = "default_task_max_concurrency")]
+    pub task_max_concurrency: usize,
+    #[serde(default)]
+    pub task_type_concurrency: String, // 任务类型并发上限，例如 "system_cleanup=1,data_backup=2"
+    #[serde(default = "default_node_id")]
+    pub node_id: String,
+    #[serde(default)]
+    pub cluster_enabled: bool, // 多节点部署时通过 Redis 选举调度主节点
+    #[serde(default = "default_leader_lease_seconds")]
+    pub leader_lease_seconds: u64,
+    #[serde(default = "default_backup_dir")]
+    pub backup_dir: String, // 备份任务只能写入该目录及其子目录
+    #[serde(default = "default_uploads_dir")]
+    pub uploads_dir: String, // 上传文件目录，清理任务只能清理该目录及其子目录
+    #[serde(default = "default_program_runs_dir")]
+    pub program_runs_dir: String, // 程序运行输出被截断时保存完整内容的目录，由清理任务按保留天数清理
+    #[serde(default = "default_program_cpu_seconds")]
+    pub program_cpu_seconds: u64,
+    #[serde(default = "default_program_wall_seconds")]
+    pub program_wall_seconds: u64,
+    #[serde(default = "default_program_memory_mb")]
+    pub program_memory_mb: u64,
+    #[serde(default = "default_program_output_bytes")]
+    pub program_output_bytes: usize,
+    #[serde(default = "default_program_input_bytes")]
+    pub program_input_bytes: usize,
+    #[serde(default = "default_program_deny_network")]
+    pub program_deny_network: bool, // 在独立的网络命名空间中运行程序
+    #[serde(default = "default_program_max_processes")]
+    pub program_max_processes: u64,
+    #[serde(default = "default_program_uid")]
+    pub program_uid: u32, // 运行程序的专用低权限用户，不能为 0
+    #[serde(default = "default_program_gid")]
+    pub program_gid: u32,
 }
 
 fn default_system_email() -> String {
     "system@example.com".to_string()
 }
 
+fn default_metrics_port() -> u16 {
+    9091
+}
+
+fn default_task_max_concurrency() -> usize {
+    DEFAULT_MAX_CONCURRENCY
+}
+
+fn default_node_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+fn default_leader_lease_seconds() -> u64 {
+    15
+}
+
+fn default_backup_dir() -> String {
+    "./backups".to_string()
+}
+
+fn default_uploads_dir() -> String {
+    "./uploads".to_string()
+}
+
+fn default_program_runs_dir() -> String {
+    "./program_runs".to_string()
+}
+
+fn default_program_cpu_seconds() -> u64 {
+    SandboxLimits::default().cpu_seconds
+}
+
+fn default_program_wall_seconds() -> u64 {
+    SandboxLimits::default().wall_seconds
+}
+
+fn default_program_memory_mb() -> u64 {
+    SandboxLimits::default().memory_bytes / (1024 * 1024)
+}
+
+fn default_program_output_bytes() -> usize {
+    SandboxLimits::default().output_bytes
+}
+
+fn default_program_input_bytes() -> usize {
+    SandboxLimits::default().input_bytes
+}
+
+fn default_program_deny_network() -> bool {
+    SandboxLimits::default().deny_network
+}
+
+fn default_program_max_processes() -> u64 {
+    SandboxLimits::default().max_processes
+}
+
+fn default_program_uid() -> u32 {
+    SandboxLimits::default().uid
+}
+
+fn default_program_gid() -> u32 {
+    SandboxLimits::default().gid
+}
+
 impl Config {
     pub fn new() -> Result<Self, AppError> {
         dotenv().ok();
@@ -33,6 +134,28 @@ impl Config {
             // .add_source(Environment::with_prefix("APP"))
             .build()?
             .try_deserialize()
-            .map_err(|e| AppError::Environment(e))
+            .map_err(AppError::Environment)
+    }
+
+    /// 解析 `task_type_concurrency`，返回任务类型到并发上限的映射
+    pub fn task_type_limits(&self) -> Result<HashMap<String, usize>, AppError> {
+        self.task_type_concurrency
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                item.split_once('=')
+                    .and_then(|(task_type, limit)| {
+                        let limit = limit.trim().parse().ok()?;
+                        Some((task_type.trim().to_string(), limit))
+                    })
+                    .ok_or_else(|| {
+                        AppError::Configuration(format!(
+                            "Invalid task_type_concurrency entry: {}",
+                            item
+                        ))
+                    })
+            })
+            .collect()
     }
 }
@@ -16,6 +16,12 @@ pub enum AppError {
     #[error("Database migration error: {0}")]
     Migration(#[from] MigrateError),
 
+    #[error("Redis error: {0}")]
+    Redis(#[from] deadpool_redis::redis::RedisError),
+
+    #[error("Redis pool error: {0}")]
+    RedisPool(#[from] deadpool_redis::PoolError),
+
     #[error("Authentication error: {0}")]
     Auth(String),
 
@@ -43,6 +49,9 @@ pub enum AppError {
     #[error("External service error: {0}")]
     External(String),
 
+    #[error("Timeout: {0}")]
+    Timeout(String),
+
     #[error("Job scheduler error: {0}")]
     JobScheduler(#[from] tokio_cron_scheduler::JobSchedulerError),
 
@@ -76,6 +85,20 @@ impl IntoResponse for AppError {
                 error!(error = ?e, "Database migration error occurred");
                 (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
             }
+            AppError::Redis(ref e) => {
+                error!(error = ?e, "Redis error occurred");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "A Redis error occurred".to_string(),
+                )
+            }
+            AppError::RedisPool(ref e) => {
+                error!(error = ?e, "Redis pool error occurred");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "A Redis error occurred".to_string(),
+                )
+            }
             AppError::Auth(ref msg) => {
                 error!(error = ?msg, "Authentication error occurred");
                 (StatusCode::UNAUTHORIZED, msg.clone())
@@ -112,6 +135,10 @@ impl IntoResponse for AppError {
                 error!(error = ?msg, "External service error occurred");
                 (StatusCode::BAD_GATEWAY, msg.clone())
             }
+            AppError::Timeout(ref msg) => {
+                error!(error = ?msg, "Timeout error occurred");
+                (StatusCode::GATEWAY_TIMEOUT, msg.clone())
+            }
             AppError::JobScheduler(ref e) => {
                 error!(error = ?e, "Job scheduler error occurred");
                 (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
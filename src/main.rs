@@ -1,10 +1,20 @@
-use std::sync::Arc;
-use tracing::info;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tracing::{info, warn};
 use work_designer_server::{
     api::{init_router, AppState},
     db::init_databases,
-    services::{broadcast::MessageBroadcast, scheduler::Scheduler},
-    utils::{email::EmailService, python::PythonExecutor},
+    services::{
+        broadcast::MessageBroadcast,
+        cluster::{Cluster, LeaseStore, MemoryLeaseStore, RedisLeaseStore},
+        executor::{ExecutorRegistry, WorkerPool},
+        metrics::{serve_metrics, setup_metrics_recorder},
+        program::ProgramService,
+        scheduler::Scheduler,
+    },
+    utils::{
+        email::EmailService,
+        python::{PythonExecutor, SandboxLimits},
+    },
     AppError, Config,
 };
 
@@ -17,6 +27,10 @@ async fn main() -> Result<(), AppError> {
     let config = Config::new()?;
     info!("Configuration loaded");
 
+    let metrics_handle = setup_metrics_recorder();
+    let metrics_addr = SocketAddr::from(([127, 0, 0, 1], config.metrics_port));
+    tokio::spawn(serve_metrics(metrics_addr, metrics_handle));
+
     // Initialize database connections
     let db = init_databases(&config.database_url, &config.redis_url).await?;
     info!("Database connections initialized");
@@ -30,14 +44,53 @@ async fn main() -> Result<(), AppError> {
     )?;
     info!("Email service initialized");
 
-    let python_executor = PythonExecutor::new();
+    let python_executor = PythonExecutor::new().with_limits(SandboxLimits {
+        cpu_seconds: config.program_cpu_seconds,
+        wall_seconds: config.program_wall_seconds,
+        memory_bytes: config.program_memory_mb * 1024 * 1024,
+        output_bytes: config.program_output_bytes,
+        input_bytes: config.program_input_bytes,
+        max_processes: config.program_max_processes,
+        deny_network: config.program_deny_network,
+        uid: config.program_uid,
+        gid: config.program_gid,
+    });
+    let program_service = Arc::new(ProgramService::new(
+        db.sqlite.clone(),
+        python_executor.clone(),
+        &config.program_runs_dir,
+    ));
     info!("Python executor initialized");
 
     let broadcaster = Arc::new(MessageBroadcast::new(100));
     info!("Websocket broadcaster initialized");
 
-    let scheduler = Scheduler::new(db.sqlite.clone()).await?;
-    info!("Scheduler initialized");
+    let lease_store: Arc<dyn LeaseStore> = if config.cluster_enabled {
+        Arc::new(RedisLeaseStore::new(db.redis.clone()))
+    } else {
+        Arc::new(MemoryLeaseStore::default())
+    };
+    let cluster = Cluster::new(
+        config.node_id.clone(),
+        lease_store,
+        Duration::from_secs(config.leader_lease_seconds),
+    );
+    info!("Cluster node {} initialized", cluster.node_id());
+
+    let workers = WorkerPool::new(config.task_max_concurrency, config.task_type_limits()?);
+    let registry = ExecutorRegistry::with_builtin(
+        email_service.clone(),
+        python_executor.clone(),
+        &config.backup_dir,
+        &config.uploads_dir,
+        &config.program_runs_dir,
+    )?
+    .with_worker_pool(workers)
+    .with_node_id(config.node_id.clone())
+    .with_broadcaster(broadcaster.clone());
+    let scheduler = Scheduler::new(db.sqlite.clone(), registry, cluster.clone()).await?;
+    scheduler.lock().await.start().await?;
+    info!("Scheduler started");
 
     // Create shared application state
     let state = Arc::new(AppState {
@@ -45,6 +98,7 @@ async fn main() -> Result<(), AppError> {
         db,
         email_service,
         python_executor,
+        program_service,
         broadcaster: broadcaster.clone(),
         scheduler,
     });
@@ -58,7 +112,16 @@ async fn main() -> Result<(), AppError> {
     info!("Server starting on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app.into_make_service()).await?;
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    // 释放主节点租约，其他节点无需等待租约过期即可接管
+    if let Err(e) = cluster.resign().await {
+        warn!(error = ?e, "Failed to release leader lease");
+    }
 
     Ok(())
 }
@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
 
-use crate::api::AppState;
+use crate::{api::AppState, models::ScheduledTask};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -37,6 +37,32 @@ impl AuthUser {
     pub fn is_guest(&self) -> bool {
         self.role == "guest"
     }
+
+    /// 管理员和访客可查看所有任务，普通用户只能查看自己创建的任务；
+    /// 不能管理任务的用户看不到任务参数和执行结果，见 `can_manage_task`
+    pub fn can_view_task(&self, task: &ScheduledTask) -> bool {
+        self.can_view_tasks_of(task.created_by)
+    }
+
+    /// 能否查看所有用户创建的任务；访客不能创建任务，只读查看全部任务
+    pub fn can_view_all_tasks(&self) -> bool {
+        self.is_admin() || self.is_guest()
+    }
+
+    /// 能否查看某用户创建的任务
+    pub fn can_view_tasks_of(&self, created_by: uuid::Uuid) -> bool {
+        self.can_view_all_tasks() || created_by == self.user_id
+    }
+
+    /// 管理员可管理所有任务，普通用户只能管理自己创建的任务，访客只读
+    pub fn can_manage_task(&self, task: &ScheduledTask) -> bool {
+        self.can_manage_tasks_of(task.created_by)
+    }
+
+    /// 能否管理某用户创建的任务
+    pub fn can_manage_tasks_of(&self, created_by: uuid::Uuid) -> bool {
+        self.is_admin() || (self.is_user() && created_by == self.user_id)
+    }
 }
 
 #[async_trait]
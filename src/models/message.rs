@@ -1,12 +1,12 @@
 use serde::{Deserialize, Serialize};
 
-use super::document::DocumentUpdateMessage;
+use super::{document::DocumentUpdateMessage, task::TaskProgressMessage};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(tag = "type", content = "payload")]
 pub enum SocketPushMessage {
     SystemMetrics(),
-    TaskProgress(),
+    TaskProgress(TaskProgressMessage),
     Notification(),
     Document(DocumentUpdateMessage),
 }
@@ -35,8 +35,15 @@ pub enum MessageType {
 #[derive(Debug, Deserialize)]
 #[serde(tag = "action", rename_all = "lowercase")]
 pub enum ClientCommand {
-    Subscribe(MessageType),
-    Unsubscribe(MessageType),
+    /// `{"action": "subscribe", "type": "task"}`
+    Subscribe {
+        #[serde(rename = "type")]
+        msg_type: MessageType,
+    },
+    Unsubscribe {
+        #[serde(rename = "type")]
+        msg_type: MessageType,
+    },
     /// 带过滤条件的订阅（示例）
     SubscribeWithFilter {
         #[serde(rename = "type")]
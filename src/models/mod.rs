@@ -13,12 +13,16 @@ pub use self::document::{
 };
 pub use self::log::{ListLogsQuery, Log, LogLevel};
 pub use self::program::{
-    CreateProgramRequest, ListProgramQuery, ListProgramResponse, Program, ProgramCompileResponse,
-    ProgramExecution, ProgramStatus, UpdateProgram,
+    CreateProgramRequest, ListProgramQuery, ListProgramResponse, ListProgramRunResponse,
+    ListProgramRunsQuery, Program, ProgramCompileResponse, ProgramEventKind, ProgramExecution,
+    ProgramRun, ProgramRunCommand, ProgramRunStatus, ProgramStatus, UpdateProgram,
 };
 pub use self::task::{
-    CreateTaskRequest, ListTasksQuery, ScheduledTask, TaskAuditLog, TaskDependency, TaskExecution,
-    TaskPriority, TaskStatus, TaskType, UpdateTaskRequest,
+    CreateTaskDependencyRequest, CreateTaskRequest, ExportManifestQuery, FailedExecution,
+    ImportManifestQuery, ListAuditLogsQuery, ListExecutionsQuery, ListTasksQuery, ManifestAction,
+    ManifestChange, MisfirePolicy, ScheduledTask, TaskAuditAction, TaskAuditLog, TaskDependency,
+    TaskExecution, TaskExecutionStats, TaskManifest, TaskManifestEntry, TaskPriority,
+    TaskProgressMessage, TaskRunResult, TaskStatus, TaskType, UpdateTaskRequest,
 };
 pub use self::user::{
     CreateUserRequest, ListUsersQuery, UpdateUserPasswordRequest, UpdateUserRequest, User,
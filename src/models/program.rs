@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt;
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, sqlx::Type, PartialEq, Clone)]
@@ -14,15 +15,15 @@ pub enum ProgramStatus {
     Stopped,
 }
 
-impl ToString for ProgramStatus {
-    fn to_string(&self) -> String {
+impl fmt::Display for ProgramStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ProgramStatus::Pending => "pending".to_string(),
-            ProgramStatus::Compiling => "compiling".to_string(),
-            ProgramStatus::Compiled => "compiled".to_string(),
-            ProgramStatus::Failed => "failed".to_string(),
-            ProgramStatus::Running => "running".to_string(),
-            ProgramStatus::Stopped => "stopped".to_string(),
+            ProgramStatus::Pending => write!(f, "pending"),
+            ProgramStatus::Compiling => write!(f, "compiling"),
+            ProgramStatus::Compiled => write!(f, "compiled"),
+            ProgramStatus::Failed => write!(f, "failed"),
+            ProgramStatus::Running => write!(f, "running"),
+            ProgramStatus::Stopped => write!(f, "stopped"),
         }
     }
 }
@@ -88,24 +89,145 @@ pub struct ListProgramResponse {
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+/// 修改程序，状态只由编译更新；修改源代码后需要重新编译
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UpdateProgram {
     pub name: Option<String>,
     pub source_code: Option<String>,
-    pub status: Option<ProgramStatus>,
     pub is_active: Option<bool>,
     pub description: Option<String>,
     pub metadata: Option<Value>,
 }
 
+/// 程序运行事件的类型
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramEventKind {
+    Input,     // 写入标准输入的内容
+    Stdout,    // 一行标准输出
+    Stderr,    // 一行标准错误
+    Violation, // 触发资源限制
+    Error,     // 无法运行
+    Exit,      // 运行结束，每次运行的最后一个事件
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct ProgramExecution {
+    pub id: Uuid,                 // 程序 ID
+    pub run_id: Uuid,             // 运行 ID
+    pub sequence: u64,            // 同一次运行内从 0 递增的事件序号
+    pub kind: ProgramEventKind,   // 事件类型
+    pub line: i32,                // 所在输出流中的行号，从 1 开始
+    pub input: Option<String>,    // 输入
+    pub output: Option<String>,   // 标准输出
+    pub error: Option<String>,    // 标准错误或错误说明
+    pub exit_code: Option<i32>,   // 退出码，被信号终止时为空
+    pub duration_ms: Option<i64>, // 运行耗时
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 运行 WebSocket 上客户端发送的命令
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ProgramRunCommand {
+    /// `{"action": "input", "data": "42\n"}`，原样写入标准输入
+    Input { data: String },
+    /// 关闭标准输入
+    Eof,
+    /// 结束程序
+    Kill,
+}
+
+/// 程序运行记录的状态
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "program_run_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramRunStatus {
+    Running,       // 运行中
+    Completed,     // 退出码为 0
+    Failed,        // 退出码非 0 或被信号终止
+    Killed,        // 被客户端结束
+    LimitExceeded, // 触发资源限制
+    Error,         // 无法运行
+    Canceled,      // 所属任务超时或被取消
+}
+
+impl fmt::Display for ProgramRunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgramRunStatus::Running => write!(f, "running"),
+            ProgramRunStatus::Completed => write!(f, "completed"),
+            ProgramRunStatus::Failed => write!(f, "failed"),
+            ProgramRunStatus::Killed => write!(f, "killed"),
+            ProgramRunStatus::LimitExceeded => write!(f, "limit_exceeded"),
+            ProgramRunStatus::Error => write!(f, "error"),
+            ProgramRunStatus::Canceled => write!(f, "canceled"),
+        }
+    }
+}
+
+impl From<String> for ProgramRunStatus {
+    fn from(value: String) -> Self {
+        match value.to_lowercase().as_str() {
+            "completed" => ProgramRunStatus::Completed,
+            "failed" => ProgramRunStatus::Failed,
+            "killed" => ProgramRunStatus::Killed,
+            "limit_exceeded" => ProgramRunStatus::LimitExceeded,
+            "error" => ProgramRunStatus::Error,
+            "canceled" => ProgramRunStatus::Canceled,
+            _ => ProgramRunStatus::Running,
+        }
+    }
+}
+
+/// 一次程序运行的记录
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProgramRun {
+    pub id: Uuid,                           // 运行 ID
+    pub program_id: Uuid,                   // 程序 ID
+    pub user_id: Uuid,                      // 运行用户 ID
+    pub source_hash: String,                // 所运行源码的 SHA-256
+    pub source_code: String,                // 所运行的源码
+    pub input: Option<String>,              // 标准输入（截断）
+    pub status: ProgramRunStatus,           // 运行状态
+    pub exit_code: Option<i64>,             // 退出码，被信号终止时为空
+    pub error: Option<String>,              // 错误信息
+    pub duration_ms: Option<i64>,           // 运行耗时
+    pub cpu_user_ms: Option<i64>,           // 用户态 CPU 时间
+    pub cpu_system_ms: Option<i64>,         // 内核态 CPU 时间
+    pub max_rss_kb: Option<i64>,            // 最大常驻内存
+    pub stdout: Option<String>,             // 标准输出（截断）
+    pub stderr: Option<String>,             // 标准错误（截断）
+    pub output_truncated: bool,             // 输入或输出是否被截断
+    pub artifact_path: Option<String>,      // 截断时保存完整输入输出的文件
+    pub started_at: Option<DateTime<Utc>>,  // 开始时间
+    pub finished_at: Option<DateTime<Utc>>, // 结束时间
+}
+
+/// 运行记录列表项，不含源码和输入输出
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListProgramRunResponse {
     pub id: Uuid,
-    pub line: i32,
-    pub input: Option<String>,
-    pub output: Option<String>,
+    pub user_id: Uuid,
+    pub source_hash: String,
+    pub status: ProgramRunStatus,
+    pub exit_code: Option<i64>,
     pub error: Option<String>,
-    pub timestamp: DateTime<Utc>,
+    pub duration_ms: Option<i64>,
+    pub cpu_user_ms: Option<i64>,
+    pub cpu_system_ms: Option<i64>,
+    pub max_rss_kb: Option<i64>,
+    pub output_truncated: bool,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListProgramRunsQuery {
+    pub status: Option<ProgramRunStatus>,
+    pub user_id: Option<Uuid>,
+    pub page: Option<i64>,
+    pub size: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
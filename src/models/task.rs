@@ -1,9 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "task_status", rename_all = "lowercase")]
 pub enum TaskStatus {
     Pending,
@@ -15,16 +16,16 @@ pub enum TaskStatus {
     Canceled,
 }
 
-impl ToString for TaskStatus {
-    fn to_string(&self) -> String {
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            TaskStatus::Pending => "pending".to_string(),
-            TaskStatus::Scheduled => "scheduled".to_string(),
-            TaskStatus::Running => "running".to_string(),
-            TaskStatus::Completed => "completed".to_string(),
-            TaskStatus::Failed => "failed".to_string(),
-            TaskStatus::Paused => "paused".to_string(),
-            TaskStatus::Canceled => "canceled".to_string(),
+            TaskStatus::Pending => write!(f, "pending"),
+            TaskStatus::Scheduled => write!(f, "scheduled"),
+            TaskStatus::Running => write!(f, "running"),
+            TaskStatus::Completed => write!(f, "completed"),
+            TaskStatus::Failed => write!(f, "failed"),
+            TaskStatus::Paused => write!(f, "paused"),
+            TaskStatus::Canceled => write!(f, "canceled"),
         }
     }
 }
@@ -52,13 +53,13 @@ pub enum TaskPriority {
     Critical,
 }
 
-impl ToString for TaskPriority {
-    fn to_string(&self) -> String {
+impl fmt::Display for TaskPriority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            TaskPriority::Low => "low".to_string(),
-            TaskPriority::Medium => "medium".to_string(),
-            TaskPriority::High => "high".to_string(),
-            TaskPriority::Critical => "critical".to_string(),
+            TaskPriority::Low => write!(f, "low"),
+            TaskPriority::Medium => write!(f, "medium"),
+            TaskPriority::High => write!(f, "high"),
+            TaskPriority::Critical => write!(f, "critical"),
         }
     }
 }
@@ -74,21 +75,54 @@ impl From<String> for TaskPriority {
     }
 }
 
+/// 服务停机期间错过运行的补偿策略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "misfire_policy", rename_all = "snake_case")]
+pub enum MisfirePolicy {
+    Skip,    // 跳过错过的运行
+    RunOnce, // 补偿运行一次
+    RunAll,  // 逐次补偿，最多 misfire_max_runs 次
+}
+
+impl fmt::Display for MisfirePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MisfirePolicy::Skip => write!(f, "skip"),
+            MisfirePolicy::RunOnce => write!(f, "run_once"),
+            MisfirePolicy::RunAll => write!(f, "run_all"),
+        }
+    }
+}
+
+impl From<String> for MisfirePolicy {
+    fn from(s: String) -> Self {
+        match s.to_lowercase().as_str() {
+            "run_once" => MisfirePolicy::RunOnce,
+            "run_all" => MisfirePolicy::RunAll,
+            _ => MisfirePolicy::Skip,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TaskType {
     EmailNotification,
     DataBackup,
     SystemCleanup,
+    Program,
+    HttpRequest,
     Custom(String),
 }
 
-impl ToString for TaskType {
-    fn to_string(&self) -> String {
+impl fmt::Display for TaskType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            TaskType::EmailNotification => "email_notification".to_string(),
-            TaskType::DataBackup => "data_backup".to_string(),
-            TaskType::SystemCleanup => "system_cleanup".to_string(),
-            TaskType::Custom(name) => format!("custom_{}", name),
+            TaskType::EmailNotification => write!(f, "email_notification"),
+            TaskType::DataBackup => write!(f, "data_backup"),
+            TaskType::SystemCleanup => write!(f, "system_cleanup"),
+            TaskType::Program => write!(f, "program"),
+            TaskType::HttpRequest => write!(f, "http_request"),
+            TaskType::Custom(name) => write!(f, "custom_{}", name),
         }
     }
 }
@@ -99,11 +133,26 @@ impl From<String> for TaskType {
             "email_notification" => TaskType::EmailNotification,
             "data_backup" => TaskType::DataBackup,
             "system_cleanup" => TaskType::SystemCleanup,
-            _ => TaskType::Custom(s),
+            "program" => TaskType::Program,
+            "http_request" => TaskType::HttpRequest,
+            _ => match s.strip_prefix("custom_") {
+                Some(name) => TaskType::Custom(name.to_string()),
+                None => TaskType::Custom(s),
+            },
         }
     }
 }
 
+impl TaskType {
+    /// 作用于整个系统的数据（备份、清理、通知所有用户），只有管理员可以创建和修改
+    pub fn requires_admin(&self) -> bool {
+        matches!(
+            self,
+            TaskType::DataBackup | TaskType::SystemCleanup | TaskType::EmailNotification
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ScheduledTask {
     pub id: Uuid,                           // 任务 ID
@@ -111,6 +160,7 @@ pub struct ScheduledTask {
     pub description: Option<String>,        // 任务描述
     pub task_type: TaskType,                // 任务类型
     pub cron_expression: Option<String>,    // cron 表达式
+    pub timezone: String,                   // 时区 (IANA)
     pub one_time: bool,                     // 是否一次性任务
     pub priority: TaskPriority,             // 任务优先级
     pub timeout_seconds: Option<i64>,       // 超时时间
@@ -118,6 +168,8 @@ pub struct ScheduledTask {
     pub retry_delay_seconds: i64,           // 重试延迟时间
     pub parameters: Option<Value>,          // 任务参数
     pub status: TaskStatus,                 // 任务状态
+    pub misfire_policy: MisfirePolicy,      // 错过运行的补偿策略
+    pub misfire_max_runs: i64,              // 最多补偿次数
     pub is_active: bool,                    // 是否激活
     pub created_by: Uuid,                   // 创建者 ID
     pub created_at: Option<DateTime<Utc>>,  // 创建时间
@@ -133,6 +185,7 @@ pub struct ListTasksQuery {
     pub status: Option<TaskStatus>,
     pub priority: Option<TaskPriority>,
     pub is_active: Option<bool>,
+    pub created_by: Option<Uuid>,
     pub page: i32,
     pub size: i32,
 }
@@ -144,15 +197,19 @@ pub struct CreateTaskRequest {
     pub description: Option<String>,
     pub task_type: TaskType,
     pub cron_expression: Option<String>,
+    pub timezone: Option<String>,
     pub one_time: Option<bool>,
     pub priority: Option<TaskPriority>,
     pub timeout_seconds: Option<i32>,
     pub max_retries: Option<i32>,
     pub retry_delay_seconds: Option<i32>,
     pub parameters: Option<Value>,
+    pub misfire_policy: Option<MisfirePolicy>,
+    pub misfire_max_runs: Option<i32>,
     pub is_active: Option<bool>,
     pub status: TaskStatus,
-    pub created_by: Uuid,
+    #[serde(skip_deserializing)]
+    pub created_by: Uuid, // 由登录用户决定，忽略客户端传入的值
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -160,29 +217,92 @@ pub struct UpdateTaskRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub cron_expression: Option<String>,
+    pub timezone: Option<String>,
     pub one_time: Option<bool>,
     pub priority: Option<TaskPriority>,
     pub timeout_seconds: Option<i32>,
     pub max_retries: Option<i32>,
     pub retry_delay_seconds: Option<i32>,
     pub parameters: Option<Value>,
+    pub misfire_policy: Option<MisfirePolicy>,
+    pub misfire_max_runs: Option<i32>,
     pub is_active: Option<bool>,
     pub status: Option<TaskStatus>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TaskExecution {
-    pub id: Uuid,
+    pub id: Uuid,                            // 执行 ID
+    pub task_id: Uuid,                       // 任务 ID
+    pub status: TaskStatus,                  // 执行状态
+    pub started_at: Option<DateTime<Utc>>,   // 开始时间
+    pub completed_at: Option<DateTime<Utc>>, // 完成时间
+    pub duration_ms: Option<i64>,            // 持续时间
+    pub error_message: Option<String>,       // 错误信息
+    pub node_id: Option<String>,             // 节点 ID
+    pub attempt_number: i64,                 // 尝试次数
+    pub parameters: Option<Value>,           // 执行参数
+    pub result: Option<Value>,               // 执行结果
+    pub is_catch_up: bool,                   // 是否为补偿执行
+    pub scheduled_at: Option<DateTime<Utc>>, // 补偿执行对应的原定运行时间
+    pub created_at: Option<DateTime<Utc>>,   // 创建时间
+    pub updated_at: Option<DateTime<Utc>>,   // 更新时间
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListExecutionsQuery {
+    pub status: Option<TaskStatus>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub page: Option<i64>,
+    pub size: Option<i64>,
+}
+
+/// 任务执行统计
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskExecutionStats {
+    pub total: i64,                   // 执行总数
+    pub completed: i64,               // 成功次数
+    pub failed: i64,                  // 失败次数
+    pub canceled: i64,                // 取消次数
+    pub success_rate: f64,            // 成功率
+    pub p50_duration_ms: Option<i64>, // 耗时中位数
+    pub p95_duration_ms: Option<i64>, // 耗时 P95
+}
+
+/// 失败的任务执行，用于通知摘要
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FailedExecution {
+    pub execution_id: Uuid,                // 执行 ID
+    pub task_id: Uuid,                     // 任务 ID
+    pub task_name: String,                 // 任务名称
+    pub error_message: Option<String>,     // 错误信息
+    pub attempt_number: i64,               // 尝试次数
+    pub started_at: Option<DateTime<Utc>>, // 开始时间
+}
+
+/// 单次任务执行的结果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskRunResult {
+    pub execution_id: Uuid,
     pub task_id: Uuid,
     pub status: TaskStatus,
-    pub started_at: DateTime<Utc>,
-    pub completed_at: Option<DateTime<Utc>>,
-    pub duration_ms: Option<i32>,
+    pub duration_ms: i64,
+    pub output: Option<Value>,
     pub error_message: Option<String>,
-    pub node_id: String,
-    pub attempt_number: i32,
-    pub parameters: Option<Value>,
-    pub created_at: DateTime<Utc>,
+}
+
+/// 任务执行进度，通过 WebSocket 推送给有权查看该任务的订阅者
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskProgressMessage {
+    pub task_id: Uuid,
+    pub task_name: String,
+    pub created_by: Uuid, // 任务创建者，用于判断订阅者是否可见
+    pub execution_id: Uuid,
+    pub attempt_number: i64,
+    pub percent: Option<u8>,     // 0-100，未知时为空
+    pub message: Option<String>, // 当前阶段说明
+    pub status: TaskStatus,      // 执行中为 Running，结束时为最终状态
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -190,7 +310,39 @@ pub struct TaskDependency {
     pub id: Uuid,
     pub dependent_task_id: Uuid,
     pub prerequisite_task_id: Uuid,
-    pub created_at: DateTime<Utc>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTaskDependencyRequest {
+    pub prerequisite_task_id: Uuid,
+}
+
+/// 审计记录的操作类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskAuditAction {
+    Create,
+    Update,
+    Delete,
+    Pause,
+    Resume,
+    Trigger,
+    Cancel,
+}
+
+impl fmt::Display for TaskAuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskAuditAction::Create => write!(f, "create"),
+            TaskAuditAction::Update => write!(f, "update"),
+            TaskAuditAction::Delete => write!(f, "delete"),
+            TaskAuditAction::Pause => write!(f, "pause"),
+            TaskAuditAction::Resume => write!(f, "resume"),
+            TaskAuditAction::Trigger => write!(f, "trigger"),
+            TaskAuditAction::Cancel => write!(f, "cancel"),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -198,7 +350,88 @@ pub struct TaskAuditLog {
     pub id: Uuid,
     pub task_id: Uuid,
     pub action: String,
-    pub user_id: Option<Uuid>,
-    pub details: Value,
-    pub created_at: DateTime<Utc>,
+    pub user_id: Uuid,                     // 操作用户 ID
+    pub details: Value,                    // 变更字段: {"字段": {"from": 旧值, "to": 新值}}
+    pub created_at: Option<DateTime<Utc>>, // 操作时间
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListAuditLogsQuery {
+    pub action: Option<String>,
+    pub page: Option<i64>,
+    pub size: Option<i64>,
+}
+
+/// 任务清单，用于在不同环境之间导入导出任务
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskManifest {
+    pub version: u32,
+    pub tasks: Vec<TaskManifestEntry>,
+}
+
+/// 清单中的任务，以名称作为跨环境的唯一标识
+///
+/// 导入时为空的字段保持原值不变，新建任务时使用默认值。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskManifestEntry {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub task_type: TaskType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron_expression: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_time: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<TaskPriority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_delay_seconds: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub misfire_policy: Option<MisfirePolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub misfire_max_runs: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_active: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>, // 前置任务名称
+}
+
+/// 导入清单时对单个任务的操作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestAction {
+    Create,
+    Update,
+    Deactivate,
+    Unchanged,
+}
+
+/// 导入清单的变更计划
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestChange {
+    pub name: String,
+    pub action: ManifestAction,
+    pub task_id: Option<Uuid>, // 新建任务在试运行时为空
+    pub changes: Value,        // {"字段": {"from": 旧值, "to": 新值}}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportManifestQuery {
+    pub format: Option<String>, // json（默认）或 yaml
+    pub names: Option<String>,  // 逗号分隔的任务名称，为空时导出全部
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportManifestQuery {
+    pub format: Option<String>,
+    pub dry_run: Option<bool>,
+    pub deactivate_missing: Option<bool>, // 停用清单中不存在的任务
 }
@@ -0,0 +1,395 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_redis::{redis, Pool as RedisPool};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// 调度主节点租约的键
+const LEADER_KEY: &str = "scheduler:leader";
+
+/// 单次计划运行的锁保留时间，需大于节点间时钟偏差与主节点切换时间
+const RUN_LOCK_TTL: Duration = Duration::from_secs(3600);
+
+/// 键不存在时设置，已由同一持有者持有时续期
+const ACQUIRE_SCRIPT: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if current == false then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+    return 1
+elseif current == ARGV[1] then
+    redis.call('PEXPIRE', KEYS[1], ARGV[2])
+    return 1
+end
+return 0
+"#;
+
+/// 仅删除由同一持有者持有的键
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// 租约存储，租约在到期前只能由持有者续期或释放
+#[async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// 获取或续期租约，租约由其他持有者持有时返回 false
+    async fn acquire(&self, key: &str, holder: &str, ttl: Duration) -> Result<bool, AppError>;
+
+    /// 仅在键不存在时获取，已被持有（包括同一持有者）时返回 false，从不续期
+    async fn claim(&self, key: &str, holder: &str, ttl: Duration) -> Result<bool, AppError>;
+
+    /// 释放持有的租约
+    async fn release(&self, key: &str, holder: &str) -> Result<(), AppError>;
+}
+
+/// 基于 Redis 的租约，用于多节点部署
+pub struct RedisLeaseStore {
+    pool: RedisPool,
+}
+
+impl RedisLeaseStore {
+    pub fn new(pool: RedisPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LeaseStore for RedisLeaseStore {
+    async fn acquire(&self, key: &str, holder: &str, ttl: Duration) -> Result<bool, AppError> {
+        let mut conn = self.pool.get().await?;
+        let acquired: i64 = redis::cmd("EVAL")
+            .arg(ACQUIRE_SCRIPT)
+            .arg(1)
+            .arg(key)
+            .arg(holder)
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(acquired == 1)
+    }
+
+    async fn claim(&self, key: &str, holder: &str, ttl: Duration) -> Result<bool, AppError> {
+        let mut conn = self.pool.get().await?;
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(holder)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(claimed.is_some())
+    }
+
+    async fn release(&self, key: &str, holder: &str) -> Result<(), AppError> {
+        let mut conn = self.pool.get().await?;
+        let _: i64 = redis::cmd("EVAL")
+            .arg(RELEASE_SCRIPT)
+            .arg(1)
+            .arg(key)
+            .arg(holder)
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// 进程内租约，用于单节点部署
+///
+/// 每次获取时清理已过期的租约，计划运行的锁每次触发使用不同的键，否则会一直累积。
+#[derive(Default)]
+pub struct MemoryLeaseStore {
+    leases: Mutex<HashMap<String, (String, Instant)>>, // 键 -> (持有者, 到期时间)
+}
+
+#[async_trait]
+impl LeaseStore for MemoryLeaseStore {
+    async fn acquire(&self, key: &str, holder: &str, ttl: Duration) -> Result<bool, AppError> {
+        let mut leases = self.leases.lock().unwrap();
+        let now = Instant::now();
+        leases.retain(|_, (_, expires_at)| *expires_at > now);
+        if let Some((current, expires_at)) = leases.get(key) {
+            if current != holder && *expires_at > now {
+                return Ok(false);
+            }
+        }
+        leases.insert(key.to_string(), (holder.to_string(), now + ttl));
+        Ok(true)
+    }
+
+    async fn claim(&self, key: &str, holder: &str, ttl: Duration) -> Result<bool, AppError> {
+        let mut leases = self.leases.lock().unwrap();
+        let now = Instant::now();
+        leases.retain(|_, (_, expires_at)| *expires_at > now);
+        if leases.contains_key(key) {
+            return Ok(false);
+        }
+        leases.insert(key.to_string(), (holder.to_string(), now + ttl));
+        Ok(true)
+    }
+
+    async fn release(&self, key: &str, holder: &str) -> Result<(), AppError> {
+        let mut leases = self.leases.lock().unwrap();
+        if leases
+            .get(key)
+            .is_some_and(|(current, _)| current == holder)
+        {
+            leases.remove(key);
+        }
+        Ok(())
+    }
+}
+
+/// 多节点调度协调
+///
+/// 通过租约选举调度主节点，只有主节点触发计划运行；主节点失效后，
+/// 其他节点在租约过期时接管。每次计划运行另有一把锁，避免主节点切换期间重复触发。
+pub struct Cluster {
+    node_id: String,
+    store: Arc<dyn LeaseStore>,
+    lease_ttl: Duration,
+    is_leader: AtomicBool,
+}
+
+impl Cluster {
+    pub fn new(node_id: String, store: Arc<dyn LeaseStore>, lease_ttl: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            node_id,
+            store,
+            lease_ttl,
+            is_leader: AtomicBool::new(false),
+        })
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.is_leader.load(Ordering::SeqCst)
+    }
+
+    /// 获取或续期主节点租约，返回本节点是否为主节点
+    ///
+    /// 无法访问租约存储时视为失去主节点身份。
+    pub async fn elect(&self) -> bool {
+        let is_leader = match self
+            .store
+            .acquire(LEADER_KEY, &self.node_id, self.lease_ttl)
+            .await
+        {
+            Ok(is_leader) => is_leader,
+            Err(e) => {
+                warn!(node_id = %self.node_id, error = ?e, "Failed to renew leader lease");
+                false
+            }
+        };
+
+        let was_leader = self.is_leader.swap(is_leader, Ordering::SeqCst);
+        if is_leader && !was_leader {
+            info!(node_id = %self.node_id, "Became scheduler leader");
+        } else if !is_leader && was_leader {
+            warn!(node_id = %self.node_id, "Lost scheduler leadership");
+        }
+        is_leader
+    }
+
+    /// 在后台按租约时长的三分之一定期续期，本节点成为主节点时调用 `on_elected`
+    pub fn spawn_election(self: &Arc<Self>, on_elected: impl Fn() + Send + 'static) {
+        let cluster = self.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(cluster.lease_ttl / 3).await;
+                let was_leader = cluster.is_leader();
+                if cluster.elect().await && !was_leader {
+                    on_elected();
+                }
+            }
+        });
+    }
+
+    /// 主动释放主节点租约，便于其他节点立即接管
+    pub async fn resign(&self) -> Result<(), AppError> {
+        self.is_leader.store(false, Ordering::SeqCst);
+        self.store.release(LEADER_KEY, &self.node_id).await
+    }
+
+    /// 抢占一次计划运行，只有第一次抢占成功，同一节点再次抢占也会失败
+    pub async fn claim_run(
+        &self,
+        task_id: Uuid,
+        scheduled_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let key = format!("scheduler:run:{}:{}", task_id, scheduled_at.timestamp());
+        self.store.claim(&key, &self.node_id, RUN_LOCK_TTL).await
+    }
+
+    /// 抢占前置任务完成后触发的一轮后续任务运行
+    ///
+    /// `round` 为后续任务判断就绪时的上次执行 ID，`trigger` 为触发本次运行的前置任务执行 ID。
+    /// 多个前置任务同时完成时，它们看到的是同一轮，只有一次抢占成功；持有者记录触发执行。
+    pub async fn claim_dependent_run(
+        &self,
+        task_id: Uuid,
+        round: Option<Uuid>,
+        trigger: Uuid,
+    ) -> Result<bool, AppError> {
+        let round = round.map_or_else(|| "initial".to_string(), |id| id.to_string());
+        let key = format!("scheduler:dependent:{}:{}", task_id, round);
+        let holder = format!("{}:{}", self.node_id, trigger);
+        self.store.claim(&key, &holder, RUN_LOCK_TTL).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn lease_is_exclusive_until_released() {
+        let store = MemoryLeaseStore::default();
+        assert!(store.acquire("key", "a", TTL).await.unwrap());
+        assert!(!store.acquire("key", "b", TTL).await.unwrap());
+        // 持有者可以续期
+        assert!(store.acquire("key", "a", TTL).await.unwrap());
+
+        // 非持有者释放无效
+        store.release("key", "b").await.unwrap();
+        assert!(!store.acquire("key", "b", TTL).await.unwrap());
+
+        store.release("key", "a").await.unwrap();
+        assert!(store.acquire("key", "b", TTL).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn expired_lease_can_be_taken_over() {
+        let store = MemoryLeaseStore::default();
+        assert!(store
+            .acquire("key", "a", Duration::from_millis(10))
+            .await
+            .unwrap());
+        sleep(Duration::from_millis(20)).await;
+        assert!(store.acquire("key", "b", TTL).await.unwrap());
+        assert!(!store.acquire("key", "a", TTL).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn expired_leases_are_pruned() {
+        let store = MemoryLeaseStore::default();
+        for i in 0..100 {
+            let key = format!("scheduler:run:{}", i);
+            store
+                .acquire(&key, "a", Duration::from_millis(10))
+                .await
+                .unwrap();
+        }
+        sleep(Duration::from_millis(20)).await;
+        store.acquire("key", "a", TTL).await.unwrap();
+        assert_eq!(store.leases.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_one_node_claims_a_run() {
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::default());
+        let a = Cluster::new("a".to_string(), store.clone(), TTL);
+        let b = Cluster::new("b".to_string(), store, TTL);
+        let task_id = Uuid::new_v4();
+        let first = Utc.with_ymd_and_hms(2026, 1, 1, 3, 0, 0).unwrap();
+        let second = Utc.with_ymd_and_hms(2026, 1, 2, 3, 0, 0).unwrap();
+
+        assert!(a.claim_run(task_id, first).await.unwrap());
+        assert!(!b.claim_run(task_id, first).await.unwrap());
+        // 同一节点不能重复抢占
+        assert!(!a.claim_run(task_id, first).await.unwrap());
+        assert!(b.claim_run(task_id, second).await.unwrap());
+        assert!(!a.claim_run(task_id, second).await.unwrap());
+        // 其他任务同一时间的运行互不影响
+        assert!(b.claim_run(Uuid::new_v4(), first).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn dependent_round_is_claimed_once() {
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::default());
+        let a = Cluster::new("a".to_string(), store.clone(), TTL);
+        let b = Cluster::new("b".to_string(), store, TTL);
+        let dependent = Uuid::new_v4();
+        let round = Some(Uuid::new_v4());
+
+        // 两个前置任务在同一节点同时完成
+        assert!(a
+            .claim_dependent_run(dependent, round, Uuid::new_v4())
+            .await
+            .unwrap());
+        assert!(!a
+            .claim_dependent_run(dependent, round, Uuid::new_v4())
+            .await
+            .unwrap());
+        assert!(!b
+            .claim_dependent_run(dependent, round, Uuid::new_v4())
+            .await
+            .unwrap());
+        // 后续任务运行后进入新的一轮
+        assert!(b
+            .claim_dependent_run(dependent, Some(Uuid::new_v4()), Uuid::new_v4())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn election_reports_taking_over_leadership_once() {
+        let ttl = Duration::from_millis(90);
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::default());
+        let a = Cluster::new("a".to_string(), store.clone(), ttl);
+        let b = Cluster::new("b".to_string(), store, ttl);
+        assert!(a.elect().await);
+
+        let elected = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        b.spawn_election({
+            let elected = elected.clone();
+            move || {
+                elected.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        // 租约仍由 a 持有
+        sleep(ttl / 2).await;
+        assert_eq!(elected.load(Ordering::SeqCst), 0);
+
+        a.resign().await.unwrap();
+        sleep(ttl * 2).await;
+        assert!(b.is_leader());
+        // 续期不再视为接任
+        assert_eq!(elected.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn leadership_moves_after_resign() {
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::default());
+        let a = Cluster::new("a".to_string(), store.clone(), TTL);
+        let b = Cluster::new("b".to_string(), store, TTL);
+
+        assert!(a.elect().await);
+        assert!(!b.elect().await);
+        assert!(a.is_leader() && !b.is_leader());
+
+        a.resign().await.unwrap();
+        assert!(!a.is_leader());
+        assert!(b.elect().await);
+        assert!(!a.elect().await);
+    }
+}
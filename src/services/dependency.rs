@@ -0,0 +1,269 @@
+use crate::{
+    error::AppError,
+    models::{ScheduledTask, TaskDependency},
+    services::task,
+};
+use chrono::{DateTime, Utc};
+use sqlx::{SqliteExecutor, SqlitePool};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+pub async fn list_dependencies(
+    executor: impl SqliteExecutor<'_>,
+    task_id: Uuid,
+) -> Result<Vec<TaskDependency>, AppError> {
+    let dependencies = sqlx::query_as!(
+        TaskDependency,
+        r#"SELECT
+            id as "id: Uuid", dependent_task_id as "dependent_task_id: Uuid", prerequisite_task_id as "prerequisite_task_id: Uuid", created_at as "created_at: DateTime<Utc>"
+        FROM task_dependencies WHERE dependent_task_id = ?
+        ORDER BY created_at"#,
+        task_id
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(dependencies)
+}
+
+pub async fn add_dependency(
+    pool: &SqlitePool,
+    dependent_task_id: Uuid,
+    prerequisite_task_id: Uuid,
+) -> Result<TaskDependency, AppError> {
+    // 确认两个任务都存在
+    task::get_task(pool, dependent_task_id).await?;
+    task::get_task(pool, prerequisite_task_id).await?;
+
+    let edges = load_edges(pool).await?;
+    if edges
+        .get(&dependent_task_id)
+        .is_some_and(|prerequisites| prerequisites.contains(&prerequisite_task_id))
+    {
+        return Err(AppError::Validation(
+            "Dependency already exists".to_string(),
+        ));
+    }
+    if creates_cycle(&edges, dependent_task_id, prerequisite_task_id) {
+        return Err(AppError::Validation(
+            "Dependency would create a cycle".to_string(),
+        ));
+    }
+
+    insert_dependency(pool, dependent_task_id, prerequisite_task_id).await
+}
+
+/// 写入依赖关系，不做存在性和循环检查，由调用方保证
+pub async fn insert_dependency(
+    executor: impl SqliteExecutor<'_>,
+    dependent_task_id: Uuid,
+    prerequisite_task_id: Uuid,
+) -> Result<TaskDependency, AppError> {
+    let id = Uuid::new_v4();
+    let dependency = sqlx::query_as!(
+        TaskDependency,
+        r#"INSERT INTO task_dependencies (id, dependent_task_id, prerequisite_task_id)
+        VALUES (?, ?, ?)
+        RETURNING id as "id: Uuid", dependent_task_id as "dependent_task_id: Uuid", prerequisite_task_id as "prerequisite_task_id: Uuid", created_at as "created_at: DateTime<Utc>""#,
+        id,
+        dependent_task_id,
+        prerequisite_task_id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(dependency)
+}
+
+pub async fn remove_dependency(
+    executor: impl SqliteExecutor<'_>,
+    dependent_task_id: Uuid,
+    prerequisite_task_id: Uuid,
+) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM task_dependencies WHERE dependent_task_id = ? AND prerequisite_task_id = ?",
+        dependent_task_id,
+        prerequisite_task_id
+    )
+    .execute(executor)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Dependency not found".to_string()));
+    }
+
+    Ok(())
+}
+
+/// 删除任务时清理其所有依赖关系
+pub async fn remove_task_dependencies(
+    executor: impl SqliteExecutor<'_>,
+    task_id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "DELETE FROM task_dependencies WHERE dependent_task_id = ? OR prerequisite_task_id = ?",
+        task_id,
+        task_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// 查询依赖某任务且前置任务已全部完成的活跃任务，已暂停或已取消的任务除外
+///
+/// 同时返回每个任务判断就绪时的上次执行 ID，作为本轮运行的标识。
+pub async fn ready_dependents(
+    pool: &SqlitePool,
+    prerequisite_task_id: Uuid,
+) -> Result<Vec<(ScheduledTask, Option<Uuid>)>, AppError> {
+    let dependent_ids = sqlx::query_scalar!(
+        r#"SELECT d.dependent_task_id as "id: Uuid"
+        FROM task_dependencies d
+        JOIN tasks t ON t.id = d.dependent_task_id
+        WHERE d.prerequisite_task_id = ? AND t.is_active = 1
+        AND t.status NOT IN ('paused', 'canceled')"#,
+        prerequisite_task_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut ready = Vec::new();
+    for id in dependent_ids {
+        // 先读取上次执行再判断就绪：若期间任务已开始新一轮执行，就绪判断不会通过
+        let last_execution = last_execution_id(pool, id).await?;
+        if prerequisites_satisfied(pool, id).await? {
+            ready.push((task::get_task(pool, id).await?, last_execution));
+        }
+    }
+
+    Ok(ready)
+}
+
+/// 查询任务最近一次执行的 ID
+async fn last_execution_id(pool: &SqlitePool, task_id: Uuid) -> Result<Option<Uuid>, AppError> {
+    let id = sqlx::query_scalar!(
+        r#"SELECT id as "id: Uuid" FROM task_executions
+        WHERE task_id = ?
+        ORDER BY started_at DESC, rowid DESC
+        LIMIT 1"#,
+        task_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(id)
+}
+
+/// 判断任务的所有前置任务是否在本轮（该任务上次开始执行之后）成功完成
+pub async fn prerequisites_satisfied(pool: &SqlitePool, task_id: Uuid) -> Result<bool, AppError> {
+    let last_started = sqlx::query_scalar!(
+        r#"SELECT MAX(started_at) as "started_at: DateTime<Utc>" FROM task_executions WHERE task_id = ?"#,
+        task_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let pending = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count: i64"
+        FROM task_dependencies d
+        WHERE d.dependent_task_id = ?
+        AND NOT EXISTS (
+            SELECT 1 FROM task_executions e
+            WHERE e.task_id = d.prerequisite_task_id
+            AND e.status = 'completed'
+            AND (? IS NULL OR e.completed_at > ?)
+        )"#,
+        task_id,
+        last_started,
+        last_started
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(pending == 0)
+}
+
+/// 加载依赖图: 依赖任务 -> 前置任务列表
+pub async fn load_edges(pool: &SqlitePool) -> Result<HashMap<Uuid, Vec<Uuid>>, AppError> {
+    let rows = sqlx::query!(
+        r#"SELECT dependent_task_id as "dependent: Uuid", prerequisite_task_id as "prerequisite: Uuid" FROM task_dependencies"#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut edges: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for row in rows {
+        edges
+            .entry(row.dependent)
+            .or_default()
+            .push(row.prerequisite);
+    }
+    Ok(edges)
+}
+
+/// 新增 dependent -> prerequisite 时，若从 prerequisite 出发能回到 dependent 则成环
+fn creates_cycle(edges: &HashMap<Uuid, Vec<Uuid>>, dependent: Uuid, prerequisite: Uuid) -> bool {
+    let mut stack = vec![prerequisite];
+    let mut visited = HashSet::new();
+    while let Some(current) = stack.pop() {
+        if current == dependent {
+            return true;
+        }
+        if visited.insert(current) {
+            if let Some(next) = edges.get(&current) {
+                stack.extend(next.iter().copied());
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(Uuid, Uuid)]) -> HashMap<Uuid, Vec<Uuid>> {
+        let mut graph: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for (dependent, prerequisite) in edges {
+            graph.entry(*dependent).or_default().push(*prerequisite);
+        }
+        graph
+    }
+
+    #[test]
+    fn creates_cycle_detects_loops() {
+        let (a, b, c, d) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        // c -> b -> a
+        let edges = graph(&[(c, b), (b, a)]);
+
+        assert!(creates_cycle(&edges, a, a));
+        assert!(creates_cycle(&edges, a, b));
+        assert!(creates_cycle(&edges, a, c));
+        assert!(!creates_cycle(&edges, c, a));
+        assert!(!creates_cycle(&edges, d, c));
+        assert!(!creates_cycle(&HashMap::new(), a, b));
+    }
+
+    #[test]
+    fn creates_cycle_allows_diamonds() {
+        let (a, b, c, d) = (
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        // d 依赖 b 和 c，二者都依赖 a
+        let edges = graph(&[(d, b), (d, c), (b, a), (c, a)]);
+
+        assert!(!creates_cycle(&edges, d, a));
+        assert!(creates_cycle(&edges, a, d));
+        assert!(creates_cycle(&edges, b, d));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    error::AppError,
+    models::{FailedExecution, ListExecutionsQuery, TaskExecution, TaskExecutionStats},
+};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+pub async fn list_executions(
+    pool: &SqlitePool,
+    task_id: Uuid,
+    query: ListExecutionsQuery,
+) -> Result<Vec<TaskExecution>, AppError> {
+    let limit = query.size.unwrap_or(20).clamp(1, 100);
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1) * limit;
+
+    let executions = sqlx::query_as!(
+        TaskExecution,
+        r#"SELECT
+            id as "id: Uuid", task_id as "task_id: Uuid", status as "status: String", started_at as "started_at: DateTime<Utc>", completed_at as "completed_at: DateTime<Utc>", duration_ms, error_message, node_id, attempt_number, parameters as "parameters: Value", result as "result: Value", is_catch_up, scheduled_at as "scheduled_at: DateTime<Utc>", created_at as "created_at: DateTime<Utc>", updated_at as "updated_at: DateTime<Utc>"
+        FROM task_executions
+        WHERE task_id = ?
+        AND (? IS NULL OR status = ?)
+        AND (? IS NULL OR started_at >= ?)
+        AND (? IS NULL OR started_at <= ?)
+        ORDER BY started_at DESC
+        LIMIT ? OFFSET ?"#,
+        task_id,
+        query.status,
+        query.status,
+        query.from,
+        query.from,
+        query.to,
+        query.to,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(executions)
+}
+
+pub async fn get_execution(
+    pool: &SqlitePool,
+    task_id: Uuid,
+    id: Uuid,
+) -> Result<TaskExecution, AppError> {
+    sqlx::query_as!(
+        TaskExecution,
+        r#"SELECT
+            id as "id: Uuid", task_id as "task_id: Uuid", status as "status: String", started_at as "started_at: DateTime<Utc>", completed_at as "completed_at: DateTime<Utc>", duration_ms, error_message, node_id, attempt_number, parameters as "parameters: Value", result as "result: Value", is_catch_up, scheduled_at as "scheduled_at: DateTime<Utc>", created_at as "created_at: DateTime<Utc>", updated_at as "updated_at: DateTime<Utc>"
+        FROM task_executions WHERE id = ? AND task_id = ?"#,
+        id,
+        task_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Execution not found".to_string()))
+}
+
+/// 统计执行结果，耗时分位数只统计成功的执行
+pub async fn execution_stats(
+    pool: &SqlitePool,
+    task_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<TaskExecutionStats, AppError> {
+    let counts = sqlx::query!(
+        r#"SELECT
+            COUNT(*) as "total!: i64",
+            COALESCE(SUM(status = 'completed'), 0) as "completed!: i64",
+            COALESCE(SUM(status = 'failed'), 0) as "failed!: i64",
+            COALESCE(SUM(status = 'canceled'), 0) as "canceled!: i64"
+        FROM task_executions
+        WHERE task_id = ?
+        AND (? IS NULL OR started_at >= ?)
+        AND (? IS NULL OR started_at <= ?)"#,
+        task_id,
+        from,
+        from,
+        to,
+        to
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let durations = sqlx::query_scalar!(
+        r#"SELECT duration_ms as "duration_ms!: i64"
+        FROM task_executions
+        WHERE task_id = ? AND status = 'completed' AND duration_ms IS NOT NULL
+        AND (? IS NULL OR started_at >= ?)
+        AND (? IS NULL OR started_at <= ?)
+        ORDER BY duration_ms"#,
+        task_id,
+        from,
+        from,
+        to,
+        to
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let finished = counts.completed + counts.failed;
+    let success_rate = if finished > 0 {
+        counts.completed as f64 / finished as f64
+    } else {
+        0.0
+    };
+
+    Ok(TaskExecutionStats {
+        total: counts.total,
+        completed: counts.completed,
+        failed: counts.failed,
+        canceled: counts.canceled,
+        success_rate,
+        p50_duration_ms: percentile(&durations, 50.0),
+        p95_duration_ms: percentile(&durations, 95.0),
+    })
+}
+
+/// 查询 `since` 之后所有任务的失败执行，按开始时间倒序
+pub async fn list_failed_executions_since(
+    pool: &SqlitePool,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<FailedExecution>, AppError> {
+    let executions = sqlx::query_as!(
+        FailedExecution,
+        r#"SELECT
+            e.id as "execution_id: Uuid", e.task_id as "task_id: Uuid", t.name as task_name, e.error_message, e.attempt_number, e.started_at as "started_at: DateTime<Utc>"
+        FROM task_executions e
+        JOIN tasks t ON t.id = e.task_id
+        WHERE e.status = 'failed' AND datetime(e.started_at) >= datetime(?)
+        ORDER BY e.started_at DESC
+        LIMIT ?"#,
+        since,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(executions)
+}
+
+/// 最近秩法计算分位数，`sorted` 需已升序排列
+fn percentile(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// 删除早于 `before` 且已结束的执行记录，每次最多删除 `limit` 条
+pub async fn delete_executions_before(
+    pool: &SqlitePool,
+    before: DateTime<Utc>,
+    limit: i64,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"DELETE FROM task_executions WHERE id IN (
+            SELECT id FROM task_executions
+            WHERE status IN ('completed', 'failed', 'canceled')
+            AND datetime(created_at) < datetime(?)
+            LIMIT ?
+        )"#,
+        before,
+        limit
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn count_executions_before(
+    pool: &SqlitePool,
+    before: DateTime<Utc>,
+) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM task_executions
+        WHERE status IN ('completed', 'failed', 'canceled')
+        AND datetime(created_at) < datetime(?)"#,
+        before
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
@@ -0,0 +1,442 @@
+use axum::async_trait;
+use chrono::{Duration, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tracing::info;
+
+use super::{resolve_dir, TaskContext, TaskHandler};
+use crate::error::AppError;
+
+const BACKUP_PREFIX: &str = "backup-";
+
+/// 数据备份任务
+///
+/// 使用 `VACUUM INTO` 生成一致的在线快照，并在同目录写入 `<备份文件>.sha256` 校验文件。
+///
+/// 参数: `{"directory": "daily", "compress": false, "keep": 7, "max_age_days": 30}`
+/// - `directory`: 可选，备份根目录（`BACKUP_DIR`）下的子目录，缺省时写入备份根目录
+/// - `keep`: 保留最近的备份份数
+/// - `max_age_days`: 可选，删除早于该天数的备份（最近一份始终保留）
+pub struct DataBackupHandler {
+    base_dir: PathBuf, // 备份根目录
+}
+
+impl DataBackupHandler {
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TaskHandler for DataBackupHandler {
+    async fn execute(&self, ctx: &TaskContext) -> Result<Value, AppError> {
+        let directory = resolve_dir(
+            &self.base_dir,
+            ctx.param("directory").and_then(Value::as_str),
+        )
+        .await?;
+        let compress = ctx
+            .param("compress")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let keep = ctx
+            .param("keep")
+            .and_then(Value::as_u64)
+            .unwrap_or(7)
+            .max(1) as usize;
+        let max_age_days = ctx.param("max_age_days").and_then(Value::as_i64);
+
+        ctx.report_progress(10, "Creating snapshot").await;
+        let snapshot = directory.join(format!(
+            "{}{}.db",
+            BACKUP_PREFIX,
+            Utc::now().format("%Y%m%d%H%M%S%3f")
+        ));
+        sqlx::query("VACUUM INTO ?")
+            .bind(snapshot.to_string_lossy().to_string())
+            .execute(&ctx.pool)
+            .await?;
+
+        let artifact = if compress {
+            ctx.report_progress(50, "Compressing snapshot").await;
+            let compressed = PathBuf::from(format!("{}.gz", snapshot.display()));
+            let (source, target) = (snapshot.clone(), compressed.clone());
+            tokio::task::spawn_blocking(move || gzip(&source, &target))
+                .await
+                .map_err(|e| AppError::Server(e.to_string()))??;
+            tokio::fs::remove_file(&snapshot).await?;
+            compressed
+        } else {
+            snapshot
+        };
+
+        ctx.report_progress(75, "Writing checksum").await;
+        let checksum = sha256_file(&artifact).await?;
+        tokio::fs::write(
+            checksum_path(&artifact),
+            checksum_line(&artifact, &checksum),
+        )
+        .await?;
+        let size = tokio::fs::metadata(&artifact).await?.len();
+
+        ctx.report_progress(90, "Rotating old backups").await;
+        let removed = rotate_backups(&directory, keep, max_age_days).await?;
+
+        Ok(json!({
+            "path": artifact.to_string_lossy(),
+            "size": size,
+            "sha256": checksum,
+            "compressed": compress,
+            "removed": removed,
+        }))
+    }
+}
+
+/// 从备份恢复数据库，返回恢复后的数据库文件路径
+///
+/// 先校验备份的 sha256，解压后执行迁移，再替换 `database_url` 指向的数据库文件。
+/// 恢复期间服务需处于停止状态。
+pub async fn restore_backup(artifact: &Path, database_url: &str) -> Result<PathBuf, AppError> {
+    let expected = read_checksum(artifact).await?;
+    let actual = sha256_file(artifact).await?;
+    if expected != actual {
+        return Err(AppError::Validation(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            artifact.display(),
+            expected,
+            actual
+        )));
+    }
+
+    let options = SqliteConnectOptions::from_str(database_url)?;
+    let target = options.get_filename().to_path_buf();
+    let staging = PathBuf::from(format!("{}.restore", target.display()));
+
+    let (source, staged) = (artifact.to_path_buf(), staging.clone());
+    tokio::task::spawn_blocking(move || {
+        if source.extension().is_some_and(|ext| ext == "gz") {
+            gunzip(&source, &staged)
+        } else {
+            std::fs::copy(&source, &staged).map(|_| ())
+        }
+    })
+    .await
+    .map_err(|e| AppError::Server(e.to_string()))??;
+
+    // 备份可能早于当前版本，恢复前先补齐迁移
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(SqliteConnectOptions::new().filename(&staging))
+        .await?;
+    let migrated = sqlx::migrate!().run(&pool).await;
+    pool.close().await;
+    if let Err(e) = migrated {
+        tokio::fs::remove_file(&staging).await.ok();
+        return Err(e.into());
+    }
+
+    // 旧数据库的 WAL 文件不能应用到恢复后的数据库上
+    for suffix in ["-wal", "-shm"] {
+        let path = PathBuf::from(format!("{}{}", target.display(), suffix));
+        if tokio::fs::try_exists(&path).await? {
+            tokio::fs::remove_file(&path).await?;
+        }
+    }
+    tokio::fs::rename(&staging, &target).await?;
+
+    info!(artifact = %artifact.display(), database = %target.display(), "Database restored");
+    Ok(target)
+}
+
+/// 删除超出保留份数或保留天数的备份及其校验文件，返回被删除的备份路径
+async fn rotate_backups(
+    directory: &Path,
+    keep: usize,
+    max_age_days: Option<i64>,
+) -> Result<Vec<String>, AppError> {
+    let mut backups = Vec::new();
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(BACKUP_PREFIX) && (name.ends_with(".db") || name.ends_with(".db.gz")) {
+            let modified = entry.metadata().await?.modified()?;
+            backups.push((name, modified));
+        }
+    }
+    // 文件名包含时间戳，按名称倒序即从新到旧
+    backups.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let cutoff =
+        max_age_days.map(|days| std::time::SystemTime::from(Utc::now() - Duration::days(days)));
+    let mut removed = Vec::new();
+    for (index, (name, modified)) in backups.iter().enumerate() {
+        let expired = cutoff.is_some_and(|cutoff| *modified < cutoff);
+        if index == 0 || (index < keep && !expired) {
+            continue;
+        }
+
+        let path = directory.join(name);
+        tokio::fs::remove_file(&path).await?;
+        let checksum = checksum_path(&path);
+        if tokio::fs::try_exists(&checksum).await? {
+            tokio::fs::remove_file(&checksum).await?;
+        }
+        removed.push(path.to_string_lossy().to_string());
+    }
+
+    Ok(removed)
+}
+
+/// 计算文件的 sha256（十六进制）
+async fn sha256_file(path: &Path) -> Result<String, AppError> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+        Ok(hex::encode(hasher.finalize()))
+    })
+    .await
+    .map_err(|e| AppError::Server(e.to_string()))?
+}
+
+/// 读取 `sha256sum` 格式的校验文件
+async fn read_checksum(artifact: &Path) -> Result<String, AppError> {
+    let path = checksum_path(artifact);
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|_| AppError::NotFound(format!("Checksum file not found: {}", path.display())))?;
+    content
+        .split_whitespace()
+        .next()
+        .map(str::to_lowercase)
+        .ok_or_else(|| AppError::Validation(format!("Invalid checksum file: {}", path.display())))
+}
+
+fn checksum_path(artifact: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.sha256", artifact.display()))
+}
+
+fn checksum_line(artifact: &Path, checksum: &str) -> String {
+    let name = artifact
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("{}  {}\n", checksum, name)
+}
+
+fn gzip(source: &Path, target: &Path) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(source)?);
+    let mut encoder = GzEncoder::new(
+        BufWriter::new(File::create(target)?),
+        Compression::default(),
+    );
+    io::copy(&mut reader, &mut encoder)?;
+    encoder.finish()?.flush()
+}
+
+fn gunzip(source: &Path, target: &Path) -> io::Result<()> {
+    let mut decoder = GzDecoder::new(BufReader::new(File::open(source)?));
+    let mut writer = BufWriter::new(File::create(target)?);
+    io::copy(&mut decoder, &mut writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MisfirePolicy, ScheduledTask, TaskPriority, TaskStatus, TaskType};
+    use std::time::SystemTime;
+    use uuid::Uuid;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// 写入备份及其校验文件，`age_days` 为文件的修改时间距今的天数
+    fn write_backup(dir: &Path, timestamp: &str, age_days: i64) -> PathBuf {
+        let path = dir.join(format!("{}{}.db", BACKUP_PREFIX, timestamp));
+        std::fs::write(&path, timestamp).unwrap();
+        std::fs::write(checksum_path(&path), checksum_line(&path, "0")).unwrap();
+        let modified = SystemTime::from(Utc::now() - Duration::days(age_days));
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn rotate_keeps_the_newest_backups() {
+        let dir = scratch_dir("rotate-keep");
+        let backups: Vec<PathBuf> = ["20261013", "20261014", "20261015", "20261016"]
+            .iter()
+            .map(|timestamp| write_backup(&dir, timestamp, 0))
+            .collect();
+        std::fs::write(dir.join("notes.txt"), "unrelated").unwrap();
+
+        let mut removed = rotate_backups(&dir, 2, None).await.unwrap();
+        removed.sort();
+        let expected: Vec<String> = backups[..2]
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(removed, expected);
+        for path in &backups[..2] {
+            assert!(!path.exists());
+            assert!(!checksum_path(path).exists());
+        }
+        for path in &backups[2..] {
+            assert!(path.exists());
+            assert!(checksum_path(path).exists());
+        }
+        assert!(dir.join("notes.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rotate_removes_expired_backups_but_keeps_the_latest() {
+        let dir = scratch_dir("rotate-age");
+        let latest = write_backup(&dir, "20261001", 16);
+        let older = write_backup(&dir, "20260901", 46);
+
+        let removed = rotate_backups(&dir, 7, Some(30)).await.unwrap();
+        assert_eq!(removed, vec![older.to_string_lossy().to_string()]);
+        assert!(latest.exists());
+
+        // 最近一份即使过期也保留
+        let removed = rotate_backups(&dir, 7, Some(10)).await.unwrap();
+        assert!(removed.is_empty());
+        assert!(latest.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_checksum_accepts_sha256sum_format() {
+        let dir = scratch_dir("checksum");
+        let artifact = dir.join("backup-20261017.db");
+        std::fs::write(&artifact, "snapshot").unwrap();
+        assert!(matches!(
+            read_checksum(&artifact).await,
+            Err(AppError::NotFound(_))
+        ));
+
+        let checksum = sha256_file(&artifact).await.unwrap();
+        std::fs::write(
+            checksum_path(&artifact),
+            checksum_line(&artifact, &checksum.to_uppercase()),
+        )
+        .unwrap();
+        assert_eq!(read_checksum(&artifact).await.unwrap(), checksum);
+
+        std::fs::write(checksum_path(&artifact), "\n").unwrap();
+        assert!(matches!(
+            read_checksum(&artifact).await,
+            Err(AppError::Validation(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn restore_verifies_the_checksum_and_replaces_the_database() {
+        let dir = scratch_dir("restore");
+        let source_url = format!("sqlite://{}", dir.join("source.db").display());
+        let pool = SqlitePoolOptions::new()
+            .connect_with(
+                SqliteConnectOptions::from_str(&source_url)
+                    .unwrap()
+                    .create_if_missing(true),
+            )
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (id, username, email, password, salt) VALUES ('u1', 'backup', 'backup@example.com', '', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let ctx = TaskContext {
+            pool: pool.clone(),
+            task: ScheduledTask {
+                id: Uuid::new_v4(),
+                name: "backup".to_string(),
+                description: None,
+                task_type: TaskType::DataBackup,
+                cron_expression: None,
+                timezone: "UTC".to_string(),
+                one_time: true,
+                priority: TaskPriority::Medium,
+                timeout_seconds: None,
+                max_retries: 0,
+                retry_delay_seconds: 60,
+                parameters: Some(json!({ "compress": true })),
+                status: TaskStatus::Pending,
+                misfire_policy: MisfirePolicy::Skip,
+                misfire_max_runs: 1,
+                is_active: true,
+                created_by: Uuid::nil(),
+                created_at: None,
+                updated_at: None,
+                next_run_at: None,
+                last_run_at: None,
+            },
+            execution_id: Uuid::new_v4(),
+            attempt_number: 1,
+            broadcaster: None,
+        };
+        let output = DataBackupHandler::new(dir.join("backups"))
+            .execute(&ctx)
+            .await
+            .unwrap();
+        pool.close().await;
+        let artifact = PathBuf::from(output["path"].as_str().unwrap());
+        assert_eq!(output["compressed"], true);
+        assert!(artifact.to_string_lossy().ends_with(".db.gz"));
+
+        let target_url = format!("sqlite://{}", dir.join("target.db").display());
+        let target = restore_backup(&artifact, &target_url).await.unwrap();
+        let pool = SqlitePoolOptions::new()
+            .connect_with(SqliteConnectOptions::new().filename(&target))
+            .await
+            .unwrap();
+        let username: String = sqlx::query_scalar("SELECT username FROM users WHERE id = 'u1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(username, "backup");
+        pool.close().await;
+
+        // 校验和不匹配时不恢复
+        std::fs::write(
+            checksum_path(&artifact),
+            checksum_line(&artifact, &"0".repeat(64)),
+        )
+        .unwrap();
+        std::fs::remove_file(&target).unwrap();
+        assert!(matches!(
+            restore_backup(&artifact, &target_url).await,
+            Err(AppError::Validation(_))
+        ));
+        assert!(!target.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,494 @@
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    future::Future,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::time::sleep;
+
+use super::{resolve_dir, TaskContext, TaskHandler};
+use crate::{
+    error::AppError,
+    services::{execution, log, program, task, user},
+};
+
+/// 各表默认保留天数
+const DEFAULT_RETENTION_DAYS: [(&str, i64); 4] = [
+    ("logs", 30),
+    ("task_executions", 90),
+    ("task_logs", 180),
+    ("program_runs", 90),
+];
+
+/// 批次之间的间隔，让出 SQLite 写锁
+const BATCH_PAUSE: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// 系统清理任务
+///
+/// 参数:
+/// ```json
+/// {
+///     "retention_days": {"logs": 30, "task_executions": 90, "task_logs": 180, "program_runs": 90},
+///     "uploads_dir": "avatars",
+///     "uploads_min_age_hours": 24,
+///     "batch_size": 500,
+///     "dry_run": false
+/// }
+/// ```
+/// - `retention_days`: 各表保留天数，也可以是作用于所有表的数字；某表设为 `null` 时跳过
+/// - `program_runs` 同时清理程序运行目录（`PROGRAM_RUNS_DIR`）中早于保留期的完整输出文件
+/// - 过期的验证码总会被清理
+/// - `uploads_dir`: 可选，上传目录（`UPLOADS_DIR`）下的子目录，缺省时清理上传目录
+/// - 未被用户头像引用、且超过 `uploads_min_age_hours` 的上传文件视为孤立文件
+/// - `dry_run`: 只统计将被清理的数量，不做删除
+pub struct SystemCleanupHandler {
+    uploads_dir: PathBuf,      // 上传目录
+    program_runs_dir: PathBuf, // 程序运行的完整输出目录
+}
+
+impl SystemCleanupHandler {
+    pub fn new(uploads_dir: impl Into<PathBuf>, program_runs_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            uploads_dir: uploads_dir.into(),
+            program_runs_dir: program_runs_dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TaskHandler for SystemCleanupHandler {
+    async fn execute(&self, ctx: &TaskContext) -> Result<Value, AppError> {
+        let pool = &ctx.pool;
+        let dry_run = ctx
+            .param("dry_run")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let batch_size = ctx
+            .param("batch_size")
+            .and_then(Value::as_i64)
+            .unwrap_or(500)
+            .clamp(1, 10_000);
+
+        ctx.report_progress(0, "Purging logs").await;
+        let logs = match retention_cutoff(ctx, "logs") {
+            Some(before) => Some(
+                purge(
+                    dry_run,
+                    batch_size,
+                    log::count_logs_before(pool, before),
+                    || log::delete_logs_batch_before(pool, before, batch_size),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        ctx.report_progress(25, "Purging task executions").await;
+        let executions = match retention_cutoff(ctx, "task_executions") {
+            Some(before) => Some(
+                purge(
+                    dry_run,
+                    batch_size,
+                    execution::count_executions_before(pool, before),
+                    || execution::delete_executions_before(pool, before, batch_size),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        ctx.report_progress(50, "Purging task logs").await;
+        let task_logs = match retention_cutoff(ctx, "task_logs") {
+            Some(before) => Some(
+                purge(
+                    dry_run,
+                    batch_size,
+                    task::count_task_logs_before(pool, before),
+                    || task::delete_task_logs_before(pool, before, batch_size),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        ctx.report_progress(60, "Purging program runs").await;
+        let (program_runs, run_artifacts) = match retention_cutoff(ctx, "program_runs") {
+            Some(before) => {
+                let runs = purge(
+                    dry_run,
+                    batch_size,
+                    program::count_runs_before(pool, before),
+                    || program::delete_runs_before(pool, before, batch_size),
+                )
+                .await?;
+                let artifacts = files_modified_before(&self.program_runs_dir, before).await?;
+                if !dry_run {
+                    for path in &artifacts {
+                        tokio::fs::remove_file(path).await?;
+                    }
+                }
+                (Some(runs), Some(artifacts.len()))
+            }
+            None => (None, None),
+        };
+        ctx.report_progress(70, "Purging expired verification codes")
+            .await;
+        let verification_codes = purge(
+            dry_run,
+            batch_size,
+            user::count_expired_verification_codes(pool),
+            || user::delete_expired_verification_codes(pool, batch_size),
+        )
+        .await?;
+
+        let uploads_dir = resolve_dir(
+            &self.uploads_dir,
+            ctx.param("uploads_dir").and_then(Value::as_str),
+        )
+        .await?;
+        let min_age_hours = ctx
+            .param("uploads_min_age_hours")
+            .and_then(Value::as_i64)
+            .unwrap_or(24);
+        ctx.report_progress(85, "Removing orphaned uploads").await;
+        let orphaned = orphaned_uploads(ctx, &uploads_dir, min_age_hours).await?;
+        if !dry_run {
+            for path in &orphaned {
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+
+        Ok(json!({
+            "dry_run": dry_run,
+            "logs": logs,
+            "task_executions": executions,
+            "task_logs": task_logs,
+            "program_runs": program_runs,
+            "program_run_artifacts": run_artifacts,
+            "verification_codes": verification_codes,
+            "uploads": orphaned.len(),
+            "orphaned_uploads": orphaned
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>(),
+        }))
+    }
+}
+
+/// 按某张表的保留天数计算截止时间，未配置时使用默认值，配置为 `null` 时跳过
+fn retention_cutoff(ctx: &TaskContext, table: &str) -> Option<DateTime<Utc>> {
+    let default = DEFAULT_RETENTION_DAYS
+        .iter()
+        .find(|(name, _)| *name == table)
+        .map(|(_, days)| *days);
+
+    let days = match ctx.param("retention_days") {
+        Some(Value::Object(tables)) => match tables.get(table) {
+            Some(days) => days.as_i64(),
+            None => default,
+        },
+        Some(days) => days.as_i64().or(default),
+        None => default,
+    }?;
+    Some(Utc::now() - Duration::days(days))
+}
+
+/// 试运行时只统计数量，否则分批删除直到某一批不足 `batch_size` 条
+async fn purge<C, F, Fut>(
+    dry_run: bool,
+    batch_size: i64,
+    count: C,
+    mut delete_batch: F,
+) -> Result<u64, AppError>
+where
+    C: Future<Output = Result<i64, AppError>>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<u64, AppError>>,
+{
+    if dry_run {
+        return Ok(count.await? as u64);
+    }
+
+    let mut total = 0;
+    loop {
+        let deleted = delete_batch().await?;
+        total += deleted;
+        if deleted < batch_size as u64 {
+            return Ok(total);
+        }
+        sleep(BATCH_PAUSE).await;
+    }
+}
+
+/// 查找目录中修改时间早于 `before` 的文件，目录不存在时为空
+async fn files_modified_before(
+    directory: &Path,
+    before: DateTime<Utc>,
+) -> Result<Vec<PathBuf>, AppError> {
+    if !tokio::fs::try_exists(directory).await? {
+        return Ok(Vec::new());
+    }
+
+    let cutoff = SystemTime::from(before);
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if metadata.is_file() && metadata.modified()? < cutoff {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// 查找未被用户头像引用的上传文件
+async fn orphaned_uploads(
+    ctx: &TaskContext,
+    directory: &Path,
+    min_age_hours: i64,
+) -> Result<Vec<PathBuf>, AppError> {
+    if !tokio::fs::try_exists(directory).await? {
+        return Ok(Vec::new());
+    }
+
+    let referenced: HashSet<String> = user::list_avatar_paths(&ctx.pool)
+        .await?
+        .iter()
+        .filter_map(|avatar| Path::new(avatar).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+    // 跳过较新的文件，避免误删正在上传、尚未写入数据库的文件
+    let cutoff = SystemTime::from(Utc::now() - Duration::hours(min_age_hours));
+
+    let mut orphaned = Vec::new();
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if metadata.is_file() && metadata.modified()? < cutoff && !referenced.contains(&name) {
+            orphaned.push(entry.path());
+        }
+    }
+    orphaned.sort();
+
+    Ok(orphaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MisfirePolicy, ScheduledTask, TaskPriority, TaskStatus, TaskType};
+    use sqlx::{
+        sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+        SqlitePool,
+    };
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    async fn insert(pool: &SqlitePool, table: &str, columns: &str, values: String) {
+        let sql = format!(
+            "INSERT INTO {} (id, {}) VALUES ('{}', {})",
+            table,
+            columns,
+            Uuid::new_v4(),
+            values
+        );
+        sqlx::query(&sql).execute(pool).await.unwrap();
+    }
+
+    /// 各表写入一条过期和一条未过期的记录，以及仍在运行的过期记录；不检查外键
+    async fn seeded_pool() -> SqlitePool {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .foreign_keys(false);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+
+        let old = "datetime('now', '-400 days')";
+        let execution = "task_id, status, created_at";
+        let run = "program_id, user_id, source_hash, source_code, status, started_at";
+        for at in [old, "datetime('now')"] {
+            insert(
+                &pool,
+                "logs",
+                "message, created_at",
+                format!("'log', {}", at),
+            )
+            .await;
+            insert(
+                &pool,
+                "task_executions",
+                execution,
+                format!("'t', 'completed', {}", at),
+            )
+            .await;
+            insert(
+                &pool,
+                "task_logs",
+                "task_id, user_id, action, created_at",
+                format!("'t', 'u', 'update', {}", at),
+            )
+            .await;
+            insert(
+                &pool,
+                "program_runs",
+                run,
+                format!("'p', 'u', '', '', 'completed', {}", at),
+            )
+            .await;
+        }
+        insert(
+            &pool,
+            "task_executions",
+            execution,
+            format!("'t', 'running', {}", old),
+        )
+        .await;
+        insert(
+            &pool,
+            "program_runs",
+            run,
+            format!("'p', 'u', '', '', 'running', {}", old),
+        )
+        .await;
+        for (email, expires_at) in [
+            ("old@example.com", old),
+            ("new@example.com", "datetime('now', '+1 day')"),
+        ] {
+            let sql = format!(
+                "INSERT INTO verification_codes (email, code, expires_at) VALUES ('{}', '0', {})",
+                email, expires_at
+            );
+            sqlx::query(&sql).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    async fn count(pool: &SqlitePool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    fn context(pool: SqlitePool, parameters: Value) -> TaskContext {
+        TaskContext {
+            pool,
+            task: ScheduledTask {
+                id: Uuid::new_v4(),
+                name: "cleanup".to_string(),
+                description: None,
+                task_type: TaskType::SystemCleanup,
+                cron_expression: None,
+                timezone: "UTC".to_string(),
+                one_time: true,
+                priority: TaskPriority::Medium,
+                timeout_seconds: None,
+                max_retries: 0,
+                retry_delay_seconds: 60,
+                parameters: Some(parameters),
+                status: TaskStatus::Pending,
+                misfire_policy: MisfirePolicy::Skip,
+                misfire_max_runs: 1,
+                is_active: true,
+                created_by: Uuid::nil(),
+                created_at: None,
+                updated_at: None,
+                next_run_at: None,
+                last_run_at: None,
+            },
+            execution_id: Uuid::new_v4(),
+            attempt_number: 1,
+            broadcaster: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn purges_expired_rows_in_batches() {
+        let scratch = std::env::temp_dir().join(format!("cleanup-{}", Uuid::new_v4()));
+        let runs_dir = scratch.join("program_runs");
+        tokio::fs::create_dir_all(&runs_dir).await.unwrap();
+        let (old_artifact, new_artifact) = (runs_dir.join("old.json"), runs_dir.join("new.json"));
+        for path in [&old_artifact, &new_artifact] {
+            std::fs::write(path, "{}").unwrap();
+        }
+        std::fs::File::options()
+            .write(true)
+            .open(&old_artifact)
+            .unwrap()
+            .set_modified(SystemTime::from(Utc::now() - Duration::days(400)))
+            .unwrap();
+
+        let pool = seeded_pool().await;
+        let handler = SystemCleanupHandler::new(scratch.join("uploads"), &runs_dir);
+
+        let output = handler
+            .execute(&context(pool.clone(), json!({ "dry_run": true })))
+            .await
+            .unwrap();
+        for key in ["logs", "task_executions", "task_logs", "program_runs"] {
+            assert_eq!(output[key], 1, "{}", key);
+        }
+        assert_eq!(output["program_run_artifacts"], 1);
+        assert_eq!(output["verification_codes"], 1);
+        assert_eq!(count(&pool, "logs").await, 2);
+        assert!(old_artifact.exists());
+
+        // 每批一条，需要多批才能删完
+        let output = handler
+            .execute(&context(pool.clone(), json!({ "batch_size": 1 })))
+            .await
+            .unwrap();
+        for key in ["logs", "task_executions", "task_logs", "program_runs"] {
+            assert_eq!(output[key], 1, "{}", key);
+        }
+        assert_eq!(output["verification_codes"], 1);
+        assert_eq!(count(&pool, "logs").await, 1);
+        assert_eq!(count(&pool, "task_logs").await, 1);
+        // 仍在运行的记录不清理
+        assert_eq!(count(&pool, "task_executions").await, 2);
+        assert_eq!(count(&pool, "program_runs").await, 2);
+        assert_eq!(count(&pool, "verification_codes").await, 1);
+        assert!(!old_artifact.exists());
+        assert!(new_artifact.exists());
+
+        tokio::fs::remove_dir_all(&scratch).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn retention_can_be_overridden_or_skipped() {
+        let pool = seeded_pool().await;
+        let scratch = std::env::temp_dir().join(format!("cleanup-{}", Uuid::new_v4()));
+        let handler =
+            SystemCleanupHandler::new(scratch.join("uploads"), scratch.join("program_runs"));
+
+        let output = handler
+            .execute(&context(
+                pool.clone(),
+                json!({ "retention_days": { "logs": null, "task_logs": 1000 } }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(output["logs"], Value::Null);
+        assert_eq!(output["task_logs"], 0);
+        assert_eq!(output["task_executions"], 1);
+        assert_eq!(count(&pool, "logs").await, 2);
+        assert_eq!(count(&pool, "task_logs").await, 2);
+
+        // 数字作用于所有表
+        let output = handler
+            .execute(&context(pool.clone(), json!({ "retention_days": 1000 })))
+            .await
+            .unwrap();
+        assert_eq!(output["logs"], 0);
+        assert_eq!(output["program_runs"], 0);
+
+        tokio::fs::remove_dir_all(&scratch).await.unwrap();
+    }
+}
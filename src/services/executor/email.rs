@@ -0,0 +1,296 @@
+use axum::async_trait;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{render_template, TaskContext, TaskHandler};
+use crate::{
+    error::AppError,
+    models::UserRole,
+    services::{execution, monitor::get_system_status, user},
+    utils::email::EmailService,
+};
+
+/// 邮件通知任务
+///
+/// 参数:
+/// ```json
+/// {
+///     "to": ["ops@example.com"],
+///     "roles": ["admin"],
+///     "user_ids": ["..."],
+///     "subject": "运维日报 {{ date }}",
+///     "body": "{% for t in failed_tasks %}<p>{{ t.task_name }}: {{ t.error_message }}</p>{% endfor %}",
+///     "failed_since_hours": 24,
+///     "failed_limit": 50
+/// }
+/// ```
+/// - 收件人为 `to`、`roles`（该角色下所有启用用户）与 `user_ids` 的并集，`to` 也可以是单个地址
+/// - `subject` / `body` 为 minijinja 模板，可用变量: `date`、`now`、`task`、
+///   `failed_tasks`（最近 `failed_since_hours` 小时内的失败执行）、`system`（系统状态）
+pub struct EmailNotificationHandler {
+    email_service: EmailService,
+}
+
+impl EmailNotificationHandler {
+    pub fn new(email_service: EmailService) -> Self {
+        Self { email_service }
+    }
+}
+
+#[async_trait]
+impl TaskHandler for EmailNotificationHandler {
+    async fn execute(&self, ctx: &TaskContext) -> Result<Value, AppError> {
+        let recipients = resolve_recipients(ctx).await?;
+        let context = template_context(ctx).await?;
+
+        let subject = render_template(
+            ctx.param("subject")
+                .and_then(Value::as_str)
+                .unwrap_or(&ctx.task.name),
+            &context,
+            false,
+        )?;
+        let body = render_template(
+            ctx.param("body")
+                .and_then(Value::as_str)
+                .unwrap_or_default(),
+            &context,
+            true,
+        )?;
+
+        let mut sent = Vec::new();
+        let mut failed = Vec::new();
+        for to in &recipients {
+            match self.email_service.send_email(to, &subject, &body).await {
+                Ok(()) => sent.push(to.clone()),
+                Err(e) => {
+                    warn!("Failed to send notification to {}: {}", to, e);
+                    failed.push(json!({ "to": to, "error": e.to_string() }));
+                }
+            }
+        }
+        if sent.is_empty() {
+            return Err(AppError::External(format!(
+                "Failed to send notification to all {} recipients",
+                recipients.len()
+            )));
+        }
+
+        Ok(json!({
+            "subject": subject,
+            "recipients": sent,
+            "failed": failed,
+        }))
+    }
+}
+
+/// 合并显式地址、角色与用户 ID 指定的收件人，去重并保持顺序
+async fn resolve_recipients(ctx: &TaskContext) -> Result<Vec<String>, AppError> {
+    let mut recipients: Vec<String> = Vec::new();
+    let mut add = |email: String| {
+        if !recipients.contains(&email) {
+            recipients.push(email);
+        }
+    };
+
+    for address in string_list(ctx, "to")? {
+        add(address.to_string());
+    }
+    for role in string_list(ctx, "roles")? {
+        let role = match role {
+            "admin" => UserRole::Admin,
+            "user" => UserRole::User,
+            "guest" => UserRole::Guest,
+            _ => return Err(AppError::InvalidInput(format!("Unknown role: {}", role))),
+        };
+        user::list_emails_by_role(&ctx.pool, &role)
+            .await?
+            .into_iter()
+            .for_each(&mut add);
+    }
+    for id in string_list(ctx, "user_ids")? {
+        let id = Uuid::parse_str(id)
+            .map_err(|_| AppError::InvalidInput(format!("Invalid user id: {}", id)))?;
+        let user = user::get_user_by_id(&ctx.pool, id).await?;
+        if user.is_active {
+            add(user.email);
+        }
+    }
+
+    if recipients.is_empty() {
+        return Err(AppError::InvalidInput(
+            "No recipients: set to, roles or user_ids".to_string(),
+        ));
+    }
+    Ok(recipients)
+}
+
+/// 读取字符串或字符串数组参数
+fn string_list<'a>(ctx: &'a TaskContext, key: &str) -> Result<Vec<&'a str>, AppError> {
+    let invalid = || AppError::InvalidInput(format!("Parameter {} must be a string list", key));
+    match ctx.param(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(value)) => Ok(vec![value.as_str()]),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| value.as_str().ok_or_else(invalid))
+            .collect(),
+        Some(_) => Err(invalid()),
+    }
+}
+
+async fn template_context(ctx: &TaskContext) -> Result<Value, AppError> {
+    let now = Utc::now();
+    let since_hours = ctx
+        .param("failed_since_hours")
+        .and_then(Value::as_i64)
+        .unwrap_or(24);
+    let limit = ctx
+        .param("failed_limit")
+        .and_then(Value::as_i64)
+        .unwrap_or(50);
+    let failed_tasks = execution::list_failed_executions_since(
+        &ctx.pool,
+        now - Duration::hours(since_hours),
+        limit,
+    )
+    .await?;
+    let system = tokio::task::spawn_blocking(get_system_status)
+        .await
+        .map_err(|e| AppError::Server(e.to_string()))?;
+
+    Ok(json!({
+        "date": now.format("%Y-%m-%d").to_string(),
+        "now": now.to_rfc3339(),
+        "task": { "id": ctx.task.id, "name": ctx.task.name },
+        "failed_tasks": failed_tasks,
+        "system": system,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MisfirePolicy, ScheduledTask, TaskPriority, TaskStatus, TaskType};
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    async fn migrated_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn insert_user(pool: &SqlitePool, username: &str, role: &str, is_active: bool) -> Uuid {
+        let id = Uuid::new_v4();
+        let email = format!("{}@example.com", username);
+        sqlx::query(
+            "INSERT INTO users (id, username, email, password, salt, role, is_active) VALUES (?, ?, ?, '', '', ?, ?)",
+        )
+        .bind(id)
+        .bind(username)
+        .bind(email)
+        .bind(role)
+        .bind(is_active)
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    fn context(pool: SqlitePool, parameters: Value) -> TaskContext {
+        TaskContext {
+            pool,
+            task: ScheduledTask {
+                id: Uuid::new_v4(),
+                name: "notify".to_string(),
+                description: None,
+                task_type: TaskType::EmailNotification,
+                cron_expression: None,
+                timezone: "UTC".to_string(),
+                one_time: true,
+                priority: TaskPriority::Medium,
+                timeout_seconds: None,
+                max_retries: 0,
+                retry_delay_seconds: 60,
+                parameters: Some(parameters),
+                status: TaskStatus::Pending,
+                misfire_policy: MisfirePolicy::Skip,
+                misfire_max_runs: 1,
+                is_active: true,
+                created_by: Uuid::nil(),
+                created_at: None,
+                updated_at: None,
+                next_run_at: None,
+                last_run_at: None,
+            },
+            execution_id: Uuid::new_v4(),
+            attempt_number: 1,
+            broadcaster: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn string_list_accepts_a_string_or_an_array() {
+        let pool = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        let ctx = context(
+            pool,
+            json!({ "one": "a@example.com", "many": ["a", "b"], "empty": null, "bad": [1], "object": {} }),
+        );
+        assert_eq!(string_list(&ctx, "one").unwrap(), vec!["a@example.com"]);
+        assert_eq!(string_list(&ctx, "many").unwrap(), vec!["a", "b"]);
+        assert!(string_list(&ctx, "empty").unwrap().is_empty());
+        assert!(string_list(&ctx, "missing").unwrap().is_empty());
+        assert!(string_list(&ctx, "bad").is_err());
+        assert!(string_list(&ctx, "object").is_err());
+    }
+
+    #[tokio::test]
+    async fn recipients_are_merged_and_deduplicated() {
+        let pool = migrated_pool().await;
+        insert_user(&pool, "root", "admin", true).await;
+        insert_user(&pool, "retired", "admin", false).await;
+        let alice = insert_user(&pool, "alice", "user", true).await;
+        let bob = insert_user(&pool, "bob", "user", false).await;
+
+        let ctx = context(
+            pool,
+            json!({
+                "to": ["ops@example.com", "root@example.com"],
+                "roles": ["admin"],
+                "user_ids": [alice.to_string(), bob.to_string()],
+            }),
+        );
+        assert_eq!(
+            resolve_recipients(&ctx).await.unwrap(),
+            vec!["ops@example.com", "root@example.com", "alice@example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn recipients_reject_unknown_roles_and_empty_lists() {
+        let pool = migrated_pool().await;
+        let error = resolve_recipients(&context(pool.clone(), json!({ "roles": ["owner"] })))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::InvalidInput(_)), "{}", error);
+        assert!(error.to_string().contains("Unknown role: owner"));
+
+        let error = resolve_recipients(&context(pool.clone(), json!({ "user_ids": ["x"] })))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Invalid user id"), "{}", error);
+
+        // 角色下没有启用用户
+        assert!(
+            resolve_recipients(&context(pool, json!({ "roles": "guest" })))
+                .await
+                .is_err()
+        );
+    }
+}
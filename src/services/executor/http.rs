@@ -0,0 +1,327 @@
+use axum::async_trait;
+use chrono::Utc;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Client, Method, Url,
+};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+use super::{render_template, truncate, TaskContext, TaskHandler};
+use crate::error::AppError;
+
+/// 默认保存的响应体最大字节数
+const DEFAULT_MAX_BODY_BYTES: usize = 4 * 1024;
+
+/// 建立连接的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 任务未设置 `timeout_seconds` 时的请求超时时间
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP 请求任务
+///
+/// 参数:
+/// ```json
+/// {
+///     "method": "POST",
+///     "url": "https://example.com/hooks/daily",
+///     "headers": {"Authorization": "Bearer ..."},
+///     "body": "{\"date\": \"{{ date }}\"}",
+///     "expected_status": [200, 204],
+///     "max_body_bytes": 4096
+/// }
+/// ```
+/// - `body` 为字符串时按 minijinja 模板渲染（变量: `date`、`now`、`task`、`execution_id`），
+///   为 JSON 对象或数组时以 `application/json` 发送
+/// - `expected_status` 缺省时接受任意 2xx；其它状态码视为失败，按任务的重试配置重试
+/// - 请求超时（包括读取响应）使用任务的 `timeout_seconds`，未设置时为 30 秒
+/// - 失败时错误信息中包含状态码（如有）和耗时
+pub struct HttpRequestHandler {
+    client: Client,
+}
+
+impl HttpRequestHandler {
+    pub fn new() -> Result<Self, AppError> {
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(DEFAULT_TIMEOUT)
+            .build()
+            .map_err(|e| AppError::Configuration(format!("Failed to build HTTP client: {}", e)))?;
+        Ok(Self { client })
+    }
+}
+
+/// 解析后的请求配置
+pub struct HttpRequestConfig {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Option<Value>,
+    expected_status: Vec<u16>,
+    max_body_bytes: usize,
+}
+
+impl HttpRequestConfig {
+    fn accepts(&self, status: u16) -> bool {
+        if self.expected_status.is_empty() {
+            (200..300).contains(&status)
+        } else {
+            self.expected_status.contains(&status)
+        }
+    }
+}
+
+#[async_trait]
+impl TaskHandler for HttpRequestHandler {
+    async fn execute(&self, ctx: &TaskContext) -> Result<Value, AppError> {
+        let config = parse_http_request(ctx.task.parameters.as_ref())?;
+
+        let mut request = self
+            .client
+            .request(config.method.clone(), config.url.clone())
+            .headers(config.headers.clone());
+        if let Some(secs) = ctx.task.timeout_seconds.filter(|secs| *secs > 0) {
+            request = request.timeout(Duration::from_secs(secs as u64));
+        }
+        match &config.body {
+            Some(Value::String(template)) => {
+                let context = json!({
+                    "date": Utc::now().format("%Y-%m-%d").to_string(),
+                    "now": Utc::now().to_rfc3339(),
+                    "task": { "id": ctx.task.id, "name": ctx.task.name },
+                    "execution_id": ctx.execution_id,
+                });
+                request = request.body(render_template(template, &context, false)?);
+            }
+            Some(value) => {
+                if !config.headers.contains_key(CONTENT_TYPE) {
+                    request = request.header(CONTENT_TYPE, "application/json");
+                }
+                request = request.body(value.to_string());
+            }
+            None => {}
+        }
+
+        let started = Instant::now();
+        let elapsed_ms = || started.elapsed().as_millis() as i64;
+        let mut response = request.send().await.map_err(|e| {
+            AppError::External(format!(
+                "HTTP request to {} {} failed after {}ms: {}",
+                config.method,
+                config.url,
+                elapsed_ms(),
+                e
+            ))
+        })?;
+        let latency_ms = elapsed_ms();
+        let status = response.status().as_u16();
+
+        // 只读取需要保存的部分，避免大响应占用内存
+        let mut bytes = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await.map_err(|e| {
+            AppError::External(format!(
+                "Failed to read response body (status {}) after {}ms: {}",
+                status,
+                elapsed_ms(),
+                e
+            ))
+        })? {
+            bytes.extend_from_slice(&chunk);
+            if bytes.len() > config.max_body_bytes {
+                truncated = true;
+                break;
+            }
+        }
+        let (body, cut) = truncate(&String::from_utf8_lossy(&bytes), config.max_body_bytes);
+        let truncated = truncated || cut;
+
+        if !config.accepts(status) {
+            return Err(AppError::External(format!(
+                "Unexpected status {} from {} {} after {}ms: {}",
+                status, config.method, config.url, latency_ms, body
+            )));
+        }
+
+        Ok(json!({
+            "method": config.method.as_str(),
+            "url": config.url.as_str(),
+            "status": status,
+            "latency_ms": latency_ms,
+            "body": body,
+            "body_truncated": truncated,
+        }))
+    }
+}
+
+/// 解析并校验请求参数
+pub fn parse_http_request(parameters: Option<&Value>) -> Result<HttpRequestConfig, AppError> {
+    let param = |key: &str| parameters.and_then(|p| p.get(key));
+
+    let method = param("method")
+        .and_then(Value::as_str)
+        .unwrap_or("GET")
+        .to_uppercase();
+    let method = Method::from_bytes(method.as_bytes())
+        .map_err(|_| AppError::InvalidInput(format!("Invalid HTTP method: {}", method)))?;
+
+    let url = param("url")
+        .and_then(Value::as_str)
+        .ok_or_else(|| AppError::InvalidInput("Missing parameter: url".to_string()))?;
+    let url = Url::parse(url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| AppError::InvalidInput(format!("Invalid URL: {}", url)))?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(values) = param("headers") {
+        let values = values.as_object().ok_or_else(|| {
+            AppError::InvalidInput("Parameter headers must be an object".to_string())
+        })?;
+        for (name, value) in values {
+            let header = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| AppError::InvalidInput(format!("Invalid header name: {}", name)))?;
+            let value = value
+                .as_str()
+                .and_then(|value| HeaderValue::from_str(value).ok())
+                .ok_or_else(|| {
+                    AppError::InvalidInput(format!("Invalid value for header {}", name))
+                })?;
+            headers.insert(header, value);
+        }
+    }
+
+    let expected_status = match param("expected_status") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(codes)) => codes
+            .iter()
+            .map(|code| {
+                code.as_u64()
+                    .filter(|code| (100..600).contains(code))
+                    .map(|code| code as u16)
+                    .ok_or_else(|| AppError::InvalidInput(format!("Invalid status code: {}", code)))
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => {
+            return Err(AppError::InvalidInput(
+                "Parameter expected_status must be an array".to_string(),
+            ))
+        }
+    };
+
+    let max_body_bytes = param("max_body_bytes")
+        .and_then(Value::as_u64)
+        .map_or(DEFAULT_MAX_BODY_BYTES, |bytes| bytes as usize);
+
+    Ok(HttpRequestConfig {
+        method,
+        url,
+        headers,
+        body: param("body").filter(|body| !body.is_null()).cloned(),
+        expected_status,
+        max_body_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MisfirePolicy, ScheduledTask, TaskPriority, TaskStatus, TaskType};
+    use axum::{http::StatusCode, routing::get, Router};
+    use sqlx::SqlitePool;
+    use tokio::{net::TcpListener, time::sleep};
+    use uuid::Uuid;
+
+    /// 启动本地 HTTP 服务代替外部接口，返回其地址
+    async fn stand_in_server() -> String {
+        let app = Router::new()
+            .route("/ok", get(|| async { "hello" }))
+            .route(
+                "/unavailable",
+                get(|| async { (StatusCode::SERVICE_UNAVAILABLE, "maintenance") }),
+            )
+            .route(
+                "/slow",
+                get(|| async {
+                    sleep(Duration::from_secs(5)).await;
+                    "late"
+                }),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    fn context(parameters: Value) -> TaskContext {
+        TaskContext {
+            pool: SqlitePool::connect_lazy("sqlite::memory:").unwrap(),
+            task: ScheduledTask {
+                id: Uuid::nil(),
+                name: "webhook".to_string(),
+                description: None,
+                task_type: TaskType::HttpRequest,
+                cron_expression: None,
+                timezone: "UTC".to_string(),
+                one_time: true,
+                priority: TaskPriority::Medium,
+                timeout_seconds: None,
+                max_retries: 0,
+                retry_delay_seconds: 60,
+                parameters: Some(parameters),
+                status: TaskStatus::Running,
+                misfire_policy: MisfirePolicy::Skip,
+                misfire_max_runs: 1,
+                is_active: true,
+                created_by: Uuid::nil(),
+                created_at: None,
+                updated_at: None,
+                next_run_at: None,
+                last_run_at: None,
+            },
+            execution_id: Uuid::nil(),
+            attempt_number: 1,
+            broadcaster: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_status_and_body() {
+        let base = stand_in_server().await;
+        let handler = HttpRequestHandler::new().unwrap();
+        let output = handler
+            .execute(&context(json!({ "url": format!("{}/ok", base) })))
+            .await
+            .unwrap();
+        assert_eq!(output["status"], 200);
+        assert_eq!(output["body"], "hello");
+        assert_eq!(output["body_truncated"], false);
+        assert!(output["latency_ms"].as_i64().is_some());
+    }
+
+    #[tokio::test]
+    async fn unexpected_status_reports_status_and_latency() {
+        let base = stand_in_server().await;
+        let handler = HttpRequestHandler::new().unwrap();
+        let error = handler
+            .execute(&context(json!({ "url": format!("{}/unavailable", base) })))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Unexpected status 503"), "{}", error);
+        assert!(error.contains("ms: maintenance"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn slow_response_times_out() {
+        let base = stand_in_server().await;
+        let handler = HttpRequestHandler::new().unwrap();
+        let mut ctx = context(json!({ "url": format!("{}/slow", base) }));
+        ctx.task.timeout_seconds = Some(1);
+        let started = Instant::now();
+        let error = handler.execute(&ctx).await.unwrap_err().to_string();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(error.contains("failed after"), "{}", error);
+    }
+}
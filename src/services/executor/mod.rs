@@ -0,0 +1,817 @@
+mod backup;
+mod cleanup;
+mod email;
+mod http;
+mod pool;
+mod program;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::{AbortHandle, Abortable};
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::time::{sleep, timeout};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    models::{
+        message::SocketPushMessage, ScheduledTask, TaskExecution, TaskProgressMessage,
+        TaskRunResult, TaskStatus, TaskType,
+    },
+    services::{broadcast::MessageBroadcast, task},
+    utils::{cron, email::EmailService, python::PythonExecutor},
+};
+
+pub use self::backup::{restore_backup, DataBackupHandler};
+pub use self::cleanup::SystemCleanupHandler;
+pub use self::email::EmailNotificationHandler;
+pub use self::http::{parse_http_request, HttpRequestHandler};
+pub use self::pool::{WorkerPermit, WorkerPool, DEFAULT_MAX_CONCURRENCY};
+pub use self::program::{parse_program_id, ProgramTaskHandler};
+
+/// 任务执行上下文
+#[derive(Clone)]
+pub struct TaskContext {
+    pub pool: SqlitePool,
+    pub task: ScheduledTask,
+    pub execution_id: Uuid,
+    pub attempt_number: i64,
+    broadcaster: Option<Arc<MessageBroadcast>>,
+}
+
+impl TaskContext {
+    /// 读取任务参数中的字段
+    pub fn param(&self, key: &str) -> Option<&Value> {
+        self.task.parameters.as_ref().and_then(|p| p.get(key))
+    }
+
+    /// 推送执行进度，`percent` 超过 100 时按 100 处理
+    pub async fn report_progress(&self, percent: u8, message: impl Into<String>) {
+        self.publish_progress(
+            Some(percent.min(100)),
+            Some(message.into()),
+            TaskStatus::Running,
+        )
+        .await;
+    }
+
+    async fn publish_progress(
+        &self,
+        percent: Option<u8>,
+        message: Option<String>,
+        status: TaskStatus,
+    ) {
+        let Some(broadcaster) = &self.broadcaster else {
+            return;
+        };
+        // 没有订阅者时发送会失败，进度推送不影响任务执行
+        let _ = broadcaster
+            .publish(SocketPushMessage::TaskProgress(TaskProgressMessage {
+                task_id: self.task.id,
+                task_name: self.task.name.clone(),
+                created_by: self.task.created_by,
+                execution_id: self.execution_id,
+                attempt_number: self.attempt_number,
+                percent,
+                message,
+                status,
+            }))
+            .await;
+    }
+}
+
+/// 任务处理器，每种 `TaskType` 对应一个
+#[async_trait]
+pub trait TaskHandler: Send + Sync {
+    async fn execute(&self, ctx: &TaskContext) -> Result<Value, AppError>;
+}
+
+/// 正在执行的任务
+struct RunningExecution {
+    task_id: Uuid,
+    abort_handle: AbortHandle,
+}
+
+/// 任务类型到处理器的注册表，同时跟踪正在执行的任务
+#[derive(Clone, Default)]
+pub struct ExecutorRegistry {
+    handlers: HashMap<String, Arc<dyn TaskHandler>>,
+    running: Arc<Mutex<HashMap<Uuid, RunningExecution>>>, // 执行 ID -> 执行，同一任务可能同时有多次执行
+    workers: WorkerPool,
+    node_id: Option<String>,                    // 记录到执行中的节点 ID
+    broadcaster: Option<Arc<MessageBroadcast>>, // 推送执行进度
+}
+
+impl ExecutorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册内置任务处理器，备份和清理任务只能访问 `backup_dir`、`uploads_dir` 之下的目录；
+    /// 程序任务的完整输出写入 `program_runs_dir`，由清理任务按保留天数清理
+    pub fn with_builtin(
+        email_service: EmailService,
+        python_executor: PythonExecutor,
+        backup_dir: impl Into<PathBuf>,
+        uploads_dir: impl Into<PathBuf>,
+        program_runs_dir: impl Into<PathBuf>,
+    ) -> Result<Self, AppError> {
+        let program_runs_dir = program_runs_dir.into();
+        let mut registry = Self::new();
+        registry.register(
+            TaskType::EmailNotification,
+            EmailNotificationHandler::new(email_service),
+        );
+        registry.register(TaskType::DataBackup, DataBackupHandler::new(backup_dir));
+        registry.register(
+            TaskType::SystemCleanup,
+            SystemCleanupHandler::new(uploads_dir, program_runs_dir.clone()),
+        );
+        registry.register(
+            TaskType::Program,
+            ProgramTaskHandler::new(python_executor, program_runs_dir),
+        );
+        registry.register(TaskType::HttpRequest, HttpRequestHandler::new()?);
+        Ok(registry)
+    }
+
+    /// 使用指定的执行池限制并发
+    pub fn with_worker_pool(mut self, workers: WorkerPool) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn with_node_id(mut self, node_id: impl Into<String>) -> Self {
+        self.node_id = Some(node_id.into());
+        self
+    }
+
+    /// 通过 WebSocket 广播推送执行进度
+    pub fn with_broadcaster(mut self, broadcaster: Arc<MessageBroadcast>) -> Self {
+        self.broadcaster = Some(broadcaster);
+        self
+    }
+
+    pub fn register(&mut self, task_type: TaskType, handler: impl TaskHandler + 'static) {
+        self.handlers
+            .insert(task_type.to_string(), Arc::new(handler));
+    }
+
+    pub fn get(&self, task_type: &TaskType) -> Option<Arc<dyn TaskHandler>> {
+        self.handlers.get(&task_type.to_string()).cloned()
+    }
+
+    /// 中止任务所有正在进行的执行，返回被中止的执行 ID
+    pub fn cancel(&self, task_id: Uuid) -> Vec<Uuid> {
+        let mut running = self.running.lock().unwrap();
+        let execution_ids: Vec<Uuid> = running
+            .iter()
+            .filter(|(_, execution)| execution.task_id == task_id)
+            .map(|(execution_id, _)| *execution_id)
+            .collect();
+        for execution_id in &execution_ids {
+            if let Some(execution) = running.remove(execution_id) {
+                execution.abort_handle.abort();
+            }
+        }
+        execution_ids
+    }
+}
+
+/// 执行任务，失败时按 `max_retries` / `retry_delay_seconds` 重试
+///
+/// 每次尝试都会在 `task_executions` 中记录一行，任务状态仅在重试耗尽后置为失败。
+pub async fn execute_task(
+    pool: &SqlitePool,
+    registry: &ExecutorRegistry,
+    task: ScheduledTask,
+) -> Result<TaskRunResult, AppError> {
+    run_with_retries(pool, registry, task, None).await
+}
+
+/// 补偿执行停机期间错过的一次运行，`scheduled_at` 为原定运行时间
+pub async fn execute_catch_up(
+    pool: &SqlitePool,
+    registry: &ExecutorRegistry,
+    task: ScheduledTask,
+    scheduled_at: DateTime<Utc>,
+) -> Result<TaskRunResult, AppError> {
+    run_with_retries(pool, registry, task, Some(scheduled_at)).await
+}
+
+async fn run_with_retries(
+    pool: &SqlitePool,
+    registry: &ExecutorRegistry,
+    task: ScheduledTask,
+    scheduled_at: Option<DateTime<Utc>>,
+) -> Result<TaskRunResult, AppError> {
+    let max_attempts = task.max_retries.max(0) + 1;
+    let retry_delay = retry_delay(&task);
+
+    let now = Utc::now();
+    let next_run_at = task
+        .cron_expression
+        .as_deref()
+        .filter(|_| !task.one_time)
+        .and_then(|expression| cron::next_run(expression, &task.timezone, now));
+    task::record_run_times(pool, task.id, now, next_run_at).await?;
+    task::update_run_status(pool, task.id, TaskStatus::Scheduled).await?;
+
+    let mut attempt = 1;
+    loop {
+        // 每次尝试都需要先获取执行槽位，重试等待期间不占用槽位
+        let result = {
+            let _permit = registry.workers.acquire(&task).await?;
+            // 排队等待槽位期间任务可能已被暂停或取消，此时不再执行
+            let current = task::get_task(pool, task.id).await?;
+            if matches!(current.status, TaskStatus::Paused | TaskStatus::Canceled) {
+                return skip_attempt(pool, registry, &task, attempt, scheduled_at, current.status)
+                    .await;
+            }
+            task::update_run_status(pool, task.id, TaskStatus::Running).await?;
+            run_attempt(pool, registry, &task, attempt, scheduled_at).await?
+        };
+        if result.status != TaskStatus::Failed || attempt >= max_attempts {
+            task::update_run_status(pool, task.id, result.status.clone()).await?;
+            return Ok(result);
+        }
+
+        warn!(
+            task_id = %task.id,
+            attempt,
+            max_attempts,
+            "Task attempt failed, retrying in {}s",
+            retry_delay.as_secs()
+        );
+        task::update_run_status(pool, task.id, TaskStatus::Scheduled).await?;
+        sleep(retry_delay).await;
+
+        // 等待重试期间任务可能已被暂停或取消
+        let current = task::get_task(pool, task.id).await?;
+        if matches!(current.status, TaskStatus::Paused | TaskStatus::Canceled) {
+            return Ok(result);
+        }
+        attempt += 1;
+    }
+}
+
+/// 失败后重试前的等待时间，每次重试间隔相同，负数按 0 处理
+fn retry_delay(task: &ScheduledTask) -> Duration {
+    Duration::from_secs(task.retry_delay_seconds.max(0) as u64)
+}
+
+/// 记录一次因任务已暂停或取消而未开始的尝试
+async fn skip_attempt(
+    pool: &SqlitePool,
+    registry: &ExecutorRegistry,
+    task: &ScheduledTask,
+    attempt: i64,
+    scheduled_at: Option<DateTime<Utc>>,
+    task_status: TaskStatus,
+) -> Result<TaskRunResult, AppError> {
+    let error_message = format!("Task was {} before the run started", task_status);
+    let execution = TaskExecution {
+        id: Uuid::new_v4(),
+        task_id: task.id,
+        status: TaskStatus::Canceled,
+        started_at: None,
+        completed_at: None,
+        duration_ms: None,
+        error_message: None,
+        node_id: registry.node_id.clone(),
+        attempt_number: attempt,
+        parameters: task.parameters.clone(),
+        result: None,
+        is_catch_up: scheduled_at.is_some(),
+        scheduled_at,
+        created_at: None,
+        updated_at: None,
+    };
+    task::create_execution(pool, &execution).await?;
+    task::finish_execution(
+        pool,
+        execution.id,
+        TaskStatus::Canceled,
+        0,
+        Some(error_message.clone()),
+        None,
+    )
+    .await?;
+    warn!(task_id = %task.id, attempt, "Task {} while queued, skipping run", task_status);
+
+    Ok(TaskRunResult {
+        execution_id: execution.id,
+        task_id: task.id,
+        status: TaskStatus::Canceled,
+        duration_ms: 0,
+        output: None,
+        error_message: Some(error_message),
+    })
+}
+
+/// 执行一次任务尝试，执行期间可通过 `ExecutorRegistry::cancel` 中止
+async fn run_attempt(
+    pool: &SqlitePool,
+    registry: &ExecutorRegistry,
+    task: &ScheduledTask,
+    attempt: i64,
+    scheduled_at: Option<DateTime<Utc>>,
+) -> Result<TaskRunResult, AppError> {
+    let execution = TaskExecution {
+        id: Uuid::new_v4(),
+        task_id: task.id,
+        status: TaskStatus::Running,
+        started_at: Some(Utc::now()),
+        completed_at: None,
+        duration_ms: None,
+        error_message: None,
+        node_id: registry.node_id.clone(),
+        attempt_number: attempt,
+        parameters: task.parameters.clone(),
+        result: None,
+        is_catch_up: scheduled_at.is_some(),
+        scheduled_at,
+        created_at: None,
+        updated_at: None,
+    };
+    task::create_execution(pool, &execution).await?;
+
+    let ctx = TaskContext {
+        pool: pool.clone(),
+        task: task.clone(),
+        execution_id: execution.id,
+        attempt_number: attempt,
+        broadcaster: registry.broadcaster.clone(),
+    };
+    ctx.publish_progress(Some(0), None, TaskStatus::Running)
+        .await;
+
+    let (abort_handle, abort_registration) = AbortHandle::new_pair();
+    registry.running.lock().unwrap().insert(
+        execution.id,
+        RunningExecution {
+            task_id: task.id,
+            abort_handle,
+        },
+    );
+
+    let start = Instant::now();
+    let outcome = Abortable::new(run_handler(registry, &ctx), abort_registration).await;
+    let duration_ms = start.elapsed().as_millis() as i64;
+
+    registry.running.lock().unwrap().remove(&execution.id);
+
+    let (status, output, error_message) = match outcome {
+        Ok(Ok(output)) => {
+            info!(task_id = %ctx.task.id, attempt, duration_ms, "Task completed");
+            (TaskStatus::Completed, Some(output), None)
+        }
+        Ok(Err(e)) => {
+            error!(task_id = %ctx.task.id, attempt, error = ?e, "Task failed");
+            (TaskStatus::Failed, None, Some(e.to_string()))
+        }
+        Err(_) => {
+            warn!(task_id = %ctx.task.id, attempt, "Task canceled");
+            (
+                TaskStatus::Canceled,
+                None,
+                Some("Execution canceled".to_string()),
+            )
+        }
+    };
+
+    task::finish_execution(
+        pool,
+        execution.id,
+        status.clone(),
+        duration_ms,
+        error_message.clone(),
+        output.clone(),
+    )
+    .await?;
+    let percent = (status == TaskStatus::Completed).then_some(100);
+    ctx.publish_progress(percent, error_message.clone(), status.clone())
+        .await;
+
+    Ok(TaskRunResult {
+        execution_id: execution.id,
+        task_id: ctx.task.id,
+        status,
+        duration_ms,
+        output,
+        error_message,
+    })
+}
+
+/// 调用任务处理器，超出 `timeout_seconds` 视为失败
+async fn run_handler(registry: &ExecutorRegistry, ctx: &TaskContext) -> Result<Value, AppError> {
+    let handler = registry.get(&ctx.task.task_type).ok_or_else(|| {
+        AppError::Configuration(format!(
+            "No handler registered for task type {}",
+            ctx.task.task_type
+        ))
+    })?;
+
+    match ctx.task.timeout_seconds.filter(|t| *t > 0) {
+        Some(secs) => timeout(Duration::from_secs(secs as u64), handler.execute(ctx))
+            .await
+            .unwrap_or_else(|_| {
+                Err(AppError::Timeout(format!(
+                    "Task timed out after {} seconds",
+                    secs
+                )))
+            }),
+        None => handler.execute(ctx).await,
+    }
+}
+
+/// 截断过长的文本，保留前 `max_bytes` 字节（按字符边界）
+pub(crate) fn truncate(text: &str, max_bytes: usize) -> (String, bool) {
+    if text.len() <= max_bytes {
+        return (text.to_string(), false);
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    (text[..end].to_string(), true)
+}
+
+/// 将任务参数中的目录解析到 `base` 之下，目录不存在时创建
+///
+/// 相对路径相对 `base` 解析，绝对路径必须位于 `base` 内，不允许出现 `..`；
+/// 返回解析符号链接后的真实路径，指向 `base` 之外的链接同样被拒绝。
+pub(crate) async fn resolve_dir(base: &Path, dir: Option<&str>) -> Result<PathBuf, AppError> {
+    let outside = || {
+        AppError::InvalidInput(format!(
+            "Directory must be inside {}: {}",
+            base.display(),
+            dir.unwrap_or_default()
+        ))
+    };
+
+    tokio::fs::create_dir_all(base).await?;
+    let root = tokio::fs::canonicalize(base).await?;
+    let path = match dir.map(Path::new) {
+        None => root.clone(),
+        Some(dir) if dir.components().any(|c| c == Component::ParentDir) => return Err(outside()),
+        Some(dir) if dir.is_absolute() && !dir.starts_with(&root) => return Err(outside()),
+        Some(dir) => root.join(dir),
+    };
+
+    // 创建之前先检查已存在的最深一级祖先，避免经由符号链接在 `base` 之外创建目录
+    let mut existing = path.as_path();
+    while tokio::fs::symlink_metadata(existing).await.is_err() {
+        existing = existing.parent().ok_or_else(outside)?;
+    }
+    if !tokio::fs::canonicalize(existing).await?.starts_with(&root) {
+        return Err(outside());
+    }
+
+    tokio::fs::create_dir_all(&path).await?;
+    let path = tokio::fs::canonicalize(&path).await?;
+    if !path.starts_with(&root) {
+        return Err(outside());
+    }
+    Ok(path)
+}
+
+/// 渲染任务参数中的 minijinja 模板，`html` 为真时转义变量
+pub(crate) fn render_template(
+    template: &str,
+    context: &Value,
+    html: bool,
+) -> Result<String, AppError> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    if html {
+        env.set_auto_escape_callback(|_| AutoEscape::Html);
+    }
+    env.render_str(template, context)
+        .map_err(|e| AppError::InvalidInput(format!("Template error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MisfirePolicy, TaskPriority};
+    use futures::future::pending;
+    use serde_json::json;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use std::{
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// 前 `failures` 次执行失败，之后成功；`pause` 时在失败的同时暂停任务
+    struct FlakyHandler {
+        failures: usize,
+        calls: AtomicUsize,
+        pause: bool,
+    }
+
+    impl FlakyHandler {
+        fn new(failures: usize) -> Self {
+            Self {
+                failures,
+                calls: AtomicUsize::new(0),
+                pause: false,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl TaskHandler for FlakyHandler {
+        async fn execute(&self, ctx: &TaskContext) -> Result<Value, AppError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if self.pause {
+                sqlx::query("UPDATE tasks SET status = 'paused' WHERE id = ?")
+                    .bind(ctx.task.id)
+                    .execute(&ctx.pool)
+                    .await?;
+            }
+            if call <= self.failures {
+                return Err(AppError::External(format!("transient failure {}", call)));
+            }
+            Ok(json!({ "attempt": ctx.attempt_number }))
+        }
+    }
+
+    fn scheduled_task(name: &str, task_type: TaskType) -> ScheduledTask {
+        ScheduledTask {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            task_type,
+            cron_expression: None,
+            timezone: "UTC".to_string(),
+            one_time: true,
+            priority: TaskPriority::Medium,
+            timeout_seconds: None,
+            max_retries: 0,
+            retry_delay_seconds: 60,
+            parameters: None,
+            status: TaskStatus::Pending,
+            misfire_policy: MisfirePolicy::Skip,
+            misfire_max_runs: 1,
+            is_active: true,
+            created_by: Uuid::nil(),
+            created_at: None,
+            updated_at: None,
+            next_run_at: None,
+            last_run_at: None,
+        }
+    }
+
+    /// 写入任务并注册处理器，任务不检查外键
+    async fn setup(
+        handler: impl TaskHandler + 'static,
+        max_retries: i64,
+    ) -> (SqlitePool, ExecutorRegistry, ScheduledTask) {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .foreign_keys(false);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+
+        let task = ScheduledTask {
+            cron_expression: Some("0 0 3 * * *".to_string()),
+            max_retries,
+            retry_delay_seconds: 0,
+            ..scheduled_task("flaky", TaskType::Custom("flaky".to_string()))
+        };
+        sqlx::query(
+            "INSERT INTO tasks (id, name, task_type, cron_expression, max_retries, retry_delay_seconds, status, created_by)
+            VALUES (?, ?, ?, ?, ?, 0, 'scheduled', ?)",
+        )
+        .bind(task.id)
+        .bind(&task.name)
+        .bind(task.task_type.to_string())
+        .bind(&task.cron_expression)
+        .bind(task.max_retries)
+        .bind(task.created_by)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut registry = ExecutorRegistry::new();
+        registry.register(task.task_type.clone(), handler);
+        (pool, registry, task)
+    }
+
+    /// 按尝试次数排列的执行状态
+    async fn attempts(pool: &SqlitePool, task_id: Uuid) -> Vec<(i64, String)> {
+        sqlx::query_as(
+            "SELECT attempt_number, status FROM task_executions WHERE task_id = ? ORDER BY attempt_number",
+        )
+        .bind(task_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn task_status(pool: &SqlitePool, task_id: Uuid) -> TaskStatus {
+        task::get_task(pool, task_id).await.unwrap().status
+    }
+
+    #[test]
+    fn retry_delay_uses_the_task_setting() {
+        let task = ScheduledTask {
+            retry_delay_seconds: 30,
+            ..scheduled_task("task", TaskType::HttpRequest)
+        };
+        assert_eq!(retry_delay(&task), Duration::from_secs(30));
+        let task = ScheduledTask {
+            retry_delay_seconds: -5,
+            ..task
+        };
+        assert_eq!(retry_delay(&task), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_are_retried_until_success() {
+        let (pool, registry, task) = setup(FlakyHandler::new(2), 3).await;
+
+        let result = execute_task(&pool, &registry, task.clone()).await.unwrap();
+        assert_eq!(result.status, TaskStatus::Completed);
+        assert_eq!(result.output, Some(json!({ "attempt": 3 })));
+        assert_eq!(
+            attempts(&pool, task.id).await,
+            vec![
+                (1, "failed".to_string()),
+                (2, "failed".to_string()),
+                (3, "completed".to_string()),
+            ]
+        );
+        assert_eq!(task_status(&pool, task.id).await, TaskStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn task_fails_only_after_retries_are_exhausted() {
+        let (pool, registry, task) = setup(FlakyHandler::new(usize::MAX), 1).await;
+
+        let result = execute_task(&pool, &registry, task.clone()).await.unwrap();
+        assert_eq!(result.status, TaskStatus::Failed);
+        assert_eq!(
+            result.error_message.as_deref(),
+            Some("External service error: transient failure 2")
+        );
+        assert_eq!(attempts(&pool, task.id).await.len(), 2);
+        assert_eq!(task_status(&pool, task.id).await, TaskStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn timeouts_count_as_failed_attempts() {
+        struct Hang;
+
+        #[async_trait]
+        impl TaskHandler for Hang {
+            async fn execute(&self, _: &TaskContext) -> Result<Value, AppError> {
+                pending().await
+            }
+        }
+
+        let (pool, registry, mut task) = setup(Hang, 1).await;
+        task.timeout_seconds = Some(1);
+
+        let result = execute_task(&pool, &registry, task.clone()).await.unwrap();
+        assert_eq!(result.status, TaskStatus::Failed);
+        assert!(result
+            .error_message
+            .unwrap()
+            .contains("timed out after 1 seconds"));
+        assert_eq!(attempts(&pool, task.id).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pausing_the_task_stops_further_retries() {
+        let handler = FlakyHandler {
+            pause: true,
+            ..FlakyHandler::new(usize::MAX)
+        };
+        let (pool, registry, task) = setup(handler, 3).await;
+
+        let result = execute_task(&pool, &registry, task.clone()).await.unwrap();
+        assert_eq!(result.status, TaskStatus::Failed);
+        assert_eq!(attempts(&pool, task.id).await.len(), 1);
+        assert_eq!(task_status(&pool, task.id).await, TaskStatus::Paused);
+    }
+
+    #[tokio::test]
+    async fn runs_paused_while_queued_do_not_start() {
+        struct Unreachable;
+
+        #[async_trait]
+        impl TaskHandler for Unreachable {
+            async fn execute(&self, _: &TaskContext) -> Result<Value, AppError> {
+                panic!("handler must not run for a paused task")
+            }
+        }
+
+        let (pool, mut registry, task) = setup(Unreachable, 0).await;
+        registry.workers = WorkerPool::new(1, HashMap::new());
+        let held = registry.workers.acquire(&task).await.unwrap();
+
+        let run = tokio::spawn({
+            let (pool, registry, task) = (pool.clone(), registry.clone(), task.clone());
+            async move { execute_task(&pool, &registry, task).await }
+        });
+        task::update_task_status(&pool, task.id, TaskStatus::Paused)
+            .await
+            .unwrap();
+        drop(held);
+
+        let result = run.await.unwrap().unwrap();
+        assert_eq!(result.status, TaskStatus::Canceled);
+        assert_eq!(
+            result.error_message.as_deref(),
+            Some("Task was paused before the run started")
+        );
+        assert_eq!(
+            attempts(&pool, task.id).await,
+            [(1, "canceled".to_string())]
+        );
+        assert_eq!(task_status(&pool, task.id).await, TaskStatus::Paused);
+    }
+
+    #[tokio::test]
+    async fn cancel_aborts_every_execution_of_the_task() {
+        let registry = ExecutorRegistry::new();
+        let (task_id, other_task_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let mut executions = Vec::new();
+        for task_id in [task_id, task_id, other_task_id] {
+            let (abort_handle, abort_registration) = AbortHandle::new_pair();
+            let execution_id = Uuid::new_v4();
+            registry.running.lock().unwrap().insert(
+                execution_id,
+                RunningExecution {
+                    task_id,
+                    abort_handle,
+                },
+            );
+            let execution = tokio::spawn(Abortable::new(pending::<()>(), abort_registration));
+            executions.push((execution_id, execution));
+        }
+
+        let mut canceled = registry.cancel(task_id);
+        canceled.sort();
+        let mut expected = vec![executions[0].0, executions[1].0];
+        expected.sort();
+        assert_eq!(canceled, expected);
+
+        let (_, other) = executions.pop().unwrap();
+        for (_, execution) in executions {
+            assert!(execution.await.unwrap().is_err());
+        }
+        assert!(!other.is_finished());
+        assert_eq!(registry.running.lock().unwrap().len(), 1);
+        assert!(registry.cancel(task_id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolve_dir_stays_inside_base() {
+        let scratch = std::env::temp_dir().join(format!("resolve-dir-{}", Uuid::new_v4()));
+        let base = scratch.join("backups");
+        let root = resolve_dir(&base, None).await.unwrap();
+        assert_eq!(root, tokio::fs::canonicalize(&base).await.unwrap());
+
+        let daily = resolve_dir(&base, Some("daily/db")).await.unwrap();
+        assert_eq!(daily, root.join("daily/db"));
+        assert!(daily.is_dir());
+        let absolute = root.join("weekly");
+        assert_eq!(
+            resolve_dir(&base, absolute.to_str()).await.unwrap(),
+            absolute
+        );
+
+        for dir in ["../outside", "daily/../../outside", "/etc"] {
+            assert!(resolve_dir(&base, Some(dir)).await.is_err(), "{}", dir);
+        }
+        assert!(!scratch.join("outside").exists());
+
+        // 指向 base 之外的符号链接
+        tokio::fs::create_dir_all(scratch.join("outside"))
+            .await
+            .unwrap();
+        std::os::unix::fs::symlink(scratch.join("outside"), root.join("link")).unwrap();
+        assert!(resolve_dir(&base, Some("link")).await.is_err());
+        assert!(resolve_dir(&base, Some("link/newdir")).await.is_err());
+        assert!(!scratch.join("outside/newdir").exists());
+
+        tokio::fs::remove_dir_all(&scratch).await.unwrap();
+    }
+}
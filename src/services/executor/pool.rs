@@ -0,0 +1,320 @@
+use metrics::{gauge, histogram};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    error::AppError,
+    models::{ScheduledTask, TaskPriority},
+};
+
+/// 默认的最大并发执行数，服务可通过 `TASK_MAX_CONCURRENCY` 配置
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// 有界的任务执行池
+///
+/// 超出并发上限的运行进入等待队列，按优先级出队，同优先级先进先出；
+/// 达到任务类型并发上限的运行不会阻塞其他类型的运行。
+#[derive(Clone)]
+pub struct WorkerPool {
+    state: Arc<Mutex<PoolState>>,
+}
+
+struct PoolState {
+    max_concurrency: usize,
+    type_limits: HashMap<String, usize>, // 任务类型 -> 并发上限
+    running: usize,
+    running_by_type: HashMap<String, usize>,
+    queue: BTreeMap<(Reverse<u8>, u64), QueuedRun>, // (优先级, 入队序号) -> 等待的运行
+    next_seq: u64,
+}
+
+struct QueuedRun {
+    task_type: String,
+    priority: TaskPriority,
+    enqueued_at: Instant,
+    sender: oneshot::Sender<WorkerPermit>,
+}
+
+/// 执行槽位，释放时调度下一个等待的运行
+pub struct WorkerPermit {
+    pool: WorkerPool,
+    task_type: String,
+}
+
+impl Default for WorkerPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENCY, HashMap::new())
+    }
+}
+
+impl WorkerPool {
+    pub fn new(max_concurrency: usize, type_limits: HashMap<String, usize>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PoolState {
+                max_concurrency: max_concurrency.max(1),
+                type_limits,
+                running: 0,
+                running_by_type: HashMap::new(),
+                queue: BTreeMap::new(),
+                next_seq: 0,
+            })),
+        }
+    }
+
+    /// 获取执行槽位，没有空闲槽位时按优先级排队等待
+    pub async fn acquire(&self, task: &ScheduledTask) -> Result<WorkerPermit, AppError> {
+        let task_type = task.task_type.to_string();
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if state.can_start(&task_type) {
+                state.start(&task_type);
+                record_wait(&task.priority, &task_type, 0.0);
+                state.report();
+                return Ok(WorkerPermit {
+                    pool: self.clone(),
+                    task_type,
+                });
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.queue.insert(
+                (Reverse(priority_rank(&task.priority)), seq),
+                QueuedRun {
+                    task_type,
+                    priority: task.priority.clone(),
+                    enqueued_at: Instant::now(),
+                    sender,
+                },
+            );
+            state.report();
+            receiver
+        };
+
+        // 发送端只在出队时使用，正常情况下队列中的运行不会被丢弃
+        receiver
+            .await
+            .map_err(|_| AppError::Server("Worker pool dropped a queued run".to_string()))
+    }
+
+    fn release(&self, task_type: &str) {
+        let abandoned = {
+            let mut state = self.state.lock().unwrap();
+            state.finish(task_type);
+            self.dispatch(&mut state)
+        };
+        // 等待方已放弃的槽位在锁外释放
+        drop(abandoned);
+    }
+
+    /// 按优先级把空闲槽位分配给等待的运行，返回等待方已放弃的槽位
+    fn dispatch(&self, state: &mut PoolState) -> Vec<WorkerPermit> {
+        let mut abandoned = Vec::new();
+        loop {
+            let next = state
+                .queue
+                .iter()
+                .find(|(_, run)| state.can_start(&run.task_type))
+                .map(|(key, _)| *key);
+            let Some(key) = next else {
+                break;
+            };
+
+            let run = state.queue.remove(&key).unwrap();
+            state.start(&run.task_type);
+            record_wait(
+                &run.priority,
+                &run.task_type,
+                run.enqueued_at.elapsed().as_secs_f64(),
+            );
+            let permit = WorkerPermit {
+                pool: self.clone(),
+                task_type: run.task_type,
+            };
+            if let Err(permit) = run.sender.send(permit) {
+                abandoned.push(permit);
+            }
+        }
+        state.report();
+        abandoned
+    }
+}
+
+impl Drop for WorkerPermit {
+    fn drop(&mut self) {
+        self.pool.release(&self.task_type);
+    }
+}
+
+impl PoolState {
+    fn can_start(&self, task_type: &str) -> bool {
+        let type_available = match self.type_limits.get(task_type) {
+            Some(limit) => self.running_by_type.get(task_type).copied().unwrap_or(0) < *limit,
+            None => true,
+        };
+        self.running < self.max_concurrency && type_available
+    }
+
+    fn start(&mut self, task_type: &str) {
+        self.running += 1;
+        *self
+            .running_by_type
+            .entry(task_type.to_string())
+            .or_default() += 1;
+    }
+
+    fn finish(&mut self, task_type: &str) {
+        self.running = self.running.saturating_sub(1);
+        if let Some(count) = self.running_by_type.get_mut(task_type) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    fn report(&self) {
+        gauge!("task_queue_depth").set(self.queue.len() as f64);
+        gauge!("task_workers_running").set(self.running as f64);
+    }
+}
+
+fn priority_rank(priority: &TaskPriority) -> u8 {
+    match priority {
+        TaskPriority::Low => 0,
+        TaskPriority::Medium => 1,
+        TaskPriority::High => 2,
+        TaskPriority::Critical => 3,
+    }
+}
+
+fn record_wait(priority: &TaskPriority, task_type: &str, seconds: f64) {
+    histogram!(
+        "task_queue_wait_seconds",
+        "priority" => priority.to_string(),
+        "task_type" => task_type.to_string()
+    )
+    .record(seconds);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MisfirePolicy, TaskStatus, TaskType};
+    use std::time::Duration;
+    use tokio::{sync::mpsc, time::timeout};
+    use uuid::Uuid;
+
+    fn task(task_type: TaskType, priority: TaskPriority) -> ScheduledTask {
+        ScheduledTask {
+            id: Uuid::new_v4(),
+            name: "task".to_string(),
+            description: None,
+            task_type,
+            cron_expression: None,
+            timezone: "UTC".to_string(),
+            one_time: true,
+            priority,
+            timeout_seconds: None,
+            max_retries: 0,
+            retry_delay_seconds: 60,
+            parameters: None,
+            status: TaskStatus::Pending,
+            misfire_policy: MisfirePolicy::Skip,
+            misfire_max_runs: 1,
+            is_active: true,
+            created_by: Uuid::nil(),
+            created_at: None,
+            updated_at: None,
+            next_run_at: None,
+            last_run_at: None,
+        }
+    }
+
+    async fn wait_for_queue(pool: &WorkerPool, len: usize) {
+        while pool.state.lock().unwrap().queue.len() < len {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_runs_start_by_priority() {
+        let pool = WorkerPool::new(1, HashMap::new());
+        let held = pool
+            .acquire(&task(TaskType::HttpRequest, TaskPriority::Low))
+            .await
+            .unwrap();
+
+        let (started, mut order) = mpsc::unbounded_channel();
+        let queued = [
+            ("low", TaskPriority::Low),
+            ("high-1", TaskPriority::High),
+            ("medium", TaskPriority::Medium),
+            ("critical", TaskPriority::Critical),
+            ("high-2", TaskPriority::High),
+        ];
+        for (position, (name, priority)) in queued.into_iter().enumerate() {
+            let waiter = pool.clone();
+            let started = started.clone();
+            tokio::spawn(async move {
+                let _permit = waiter
+                    .acquire(&task(TaskType::HttpRequest, priority))
+                    .await
+                    .unwrap();
+                started.send(name).unwrap();
+            });
+            wait_for_queue(&pool, position + 1).await;
+        }
+        drop(started);
+        drop(held);
+
+        let mut names = Vec::new();
+        while let Some(name) = order.recv().await {
+            names.push(name);
+        }
+        assert_eq!(names, ["critical", "high-1", "high-2", "medium", "low"]);
+        assert_eq!(pool.state.lock().unwrap().running, 0);
+    }
+
+    #[tokio::test]
+    async fn type_limit_does_not_block_other_types() {
+        let limits = HashMap::from([(TaskType::HttpRequest.to_string(), 1)]);
+        let pool = WorkerPool::new(2, limits);
+        let held = pool
+            .acquire(&task(TaskType::HttpRequest, TaskPriority::Low))
+            .await
+            .unwrap();
+
+        let waiting = {
+            let waiter = pool.clone();
+            tokio::spawn(async move {
+                waiter
+                    .acquire(&task(TaskType::HttpRequest, TaskPriority::Critical))
+                    .await
+                    .map(|_| ())
+            })
+        };
+        wait_for_queue(&pool, 1).await;
+
+        // 同类型排队的运行不影响其他类型获取空闲槽位
+        let other = timeout(
+            Duration::from_secs(1),
+            pool.acquire(&task(TaskType::DataBackup, TaskPriority::Low)),
+        )
+        .await
+        .expect("other task types should not wait")
+        .unwrap();
+        assert!(!waiting.is_finished());
+
+        drop(other);
+        drop(held);
+        timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+}
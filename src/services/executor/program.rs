@@ -0,0 +1,144 @@
+use axum::async_trait;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::path::PathBuf;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{truncate, TaskContext, TaskHandler};
+use crate::{
+    error::AppError, models::ProgramStatus, services::program, utils::python::PythonExecutor,
+};
+
+/// 输出保存到执行记录时的最大字节数
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// 运行已保存的程序
+///
+/// 参数: `{"program_id": "...", ...}`
+/// - 程序需已编译；整个参数对象以 JSON 写入程序的标准输入
+/// - 标准输出为 JSON 时按 JSON 保存，否则保存为文本；退出码非 0 或触发资源限制视为失败
+pub struct ProgramTaskHandler {
+    python_executor: PythonExecutor,
+    runs_dir: PathBuf, // 运行输出被截断时保存完整内容的目录
+}
+
+impl ProgramTaskHandler {
+    pub fn new(python_executor: PythonExecutor, runs_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            python_executor,
+            runs_dir: runs_dir.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TaskHandler for ProgramTaskHandler {
+    async fn execute(&self, ctx: &TaskContext) -> Result<Value, AppError> {
+        let program_id = parse_program_id(ctx.task.parameters.as_ref())?;
+        let program = program::get_program(&ctx.pool, program_id).await?;
+        if !program.is_active {
+            return Err(AppError::BadRequest("Program is not active".to_string()));
+        }
+        if program.status != ProgramStatus::Compiled {
+            return Err(AppError::BadRequest(
+                "Program must be compiled first".to_string(),
+            ));
+        }
+
+        let input = ctx.task.parameters.clone().unwrap_or_else(|| json!({}));
+        let run_id = program::create_run(&ctx.pool, &program, ctx.task.created_by).await?;
+        let mut guard = RunGuard::new(ctx.pool.clone(), run_id);
+        let result = self.python_executor.run(&program.source_code, &input).await;
+        // 任务参数可能包含凭据，不保存到运行记录
+        if let Err(e) = program::finish_run(
+            &ctx.pool,
+            &self.runs_dir,
+            run_id,
+            "",
+            &result,
+            self.python_executor.limits(),
+        )
+        .await
+        {
+            warn!(run_id = %run_id, error = %e, "Failed to record program run");
+        }
+        guard.finished = true;
+        let output = result?;
+
+        let (stderr, _) = truncate(&output.stderr, MAX_OUTPUT_BYTES);
+        if let Some(violation) = output.violation {
+            return Err(AppError::External(format!(
+                "{}: {}",
+                violation.describe(self.python_executor.limits()),
+                stderr.trim_end()
+            )));
+        }
+        if !output.success() {
+            let code = output
+                .exit_code
+                .map_or_else(|| "signal".to_string(), |code| code.to_string());
+            return Err(AppError::External(format!(
+                "Program exited with {}: {}",
+                code,
+                stderr.trim_end()
+            )));
+        }
+
+        let (stdout, truncated) = truncate(&output.stdout, MAX_OUTPUT_BYTES);
+        let result = match serde_json::from_str::<Value>(&stdout) {
+            Ok(value) if !truncated => value,
+            _ => Value::String(stdout),
+        };
+        Ok(json!({
+            "program_id": program_id,
+            "run_id": run_id,
+            "exit_code": output.exit_code,
+            "duration_ms": output.duration_ms,
+            "output": result,
+            "output_truncated": truncated,
+            "stderr": stderr,
+            "network_isolated": output.network_isolated,
+        }))
+    }
+}
+
+/// 任务超时或被取消时处理器的 future 被直接丢弃，由守卫将仍在运行中的记录标记为已取消
+struct RunGuard {
+    pool: SqlitePool,
+    run_id: Uuid,
+    finished: bool,
+}
+
+impl RunGuard {
+    fn new(pool: SqlitePool, run_id: Uuid) -> Self {
+        Self {
+            pool,
+            run_id,
+            finished: false,
+        }
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let (pool, run_id) = (self.pool.clone(), self.run_id);
+        tokio::spawn(async move {
+            if let Err(e) = program::cancel_run(&pool, run_id).await {
+                warn!(run_id = %run_id, error = %e, "Failed to record canceled program run");
+            }
+        });
+    }
+}
+
+/// 读取并校验参数中的程序 ID
+pub fn parse_program_id(parameters: Option<&Value>) -> Result<Uuid, AppError> {
+    let id = parameters
+        .and_then(|p| p.get("program_id"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| AppError::InvalidInput("Missing parameter: program_id".to_string()))?;
+    Uuid::parse_str(id).map_err(|_| AppError::InvalidInput(format!("Invalid program id: {}", id)))
+}
@@ -93,6 +93,39 @@ pub async fn delete_logs_before(
     Ok(result.rows_affected())
 }
 
+/// 分批删除早于 `before` 的日志，每次最多删除 `limit` 条
+pub async fn delete_logs_batch_before(
+    pool: &SqlitePool,
+    before: chrono::DateTime<chrono::Utc>,
+    limit: i64,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM logs WHERE id IN (
+            SELECT id FROM logs WHERE datetime(created_at) < datetime(?) LIMIT ?
+        )",
+        before,
+        limit
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn count_logs_before(
+    pool: &SqlitePool,
+    before: chrono::DateTime<chrono::Utc>,
+) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM logs WHERE datetime(created_at) < datetime(?)"#,
+        before
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
 pub async fn get_log(pool: &SqlitePool, id: Uuid) -> Result<Log, AppError> {
     let log = sqlx::query_as!(
         Log,
@@ -0,0 +1,693 @@
+use serde_json::{json, Map, Value};
+use sqlx::{SqlitePool, Transaction};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    models::{
+        CreateTaskRequest, ManifestAction, ManifestChange, MisfirePolicy, ScheduledTask,
+        TaskManifest, TaskManifestEntry, TaskPriority, TaskStatus, UpdateTaskRequest,
+    },
+    services::{dependency, task},
+};
+
+/// 当前清单格式版本
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// 清单的序列化格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManifestFormat {
+    Json,
+    Yaml,
+}
+
+impl ManifestFormat {
+    /// 解析 `format` 参数，缺省为 JSON
+    pub fn parse(format: Option<&str>) -> Result<Self, AppError> {
+        match format.map(str::to_lowercase).as_deref() {
+            None | Some("json") => Ok(Self::Json),
+            Some("yaml") | Some("yml") => Ok(Self::Yaml),
+            Some(format) => Err(AppError::Validation(format!(
+                "Unsupported manifest format: {}",
+                format
+            ))),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Yaml => "application/yaml",
+        }
+    }
+
+    pub fn serialize(&self, manifest: &TaskManifest) -> Result<String, AppError> {
+        match self {
+            Self::Json => serde_json::to_string_pretty(manifest)
+                .map_err(|e| AppError::Server(format!("Failed to serialize manifest: {}", e))),
+            Self::Yaml => serde_yaml::to_string(manifest)
+                .map_err(|e| AppError::Server(format!("Failed to serialize manifest: {}", e))),
+        }
+    }
+
+    pub fn deserialize(&self, body: &str) -> Result<TaskManifest, AppError> {
+        let manifest: TaskManifest = match self {
+            Self::Json => serde_json::from_str(body)
+                .map_err(|e| AppError::Validation(format!("Invalid manifest: {}", e)))?,
+            Self::Yaml => serde_yaml::from_str(body)
+                .map_err(|e| AppError::Validation(format!("Invalid manifest: {}", e)))?,
+        };
+        if manifest.version != MANIFEST_VERSION {
+            return Err(AppError::Validation(format!(
+                "Unsupported manifest version: {}",
+                manifest.version
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+/// 导出任务清单，`names` 为空时导出 `tasks` 中的全部任务
+///
+/// 依赖关系以前置任务的名称表示，不在 `tasks` 中的前置任务不导出，避免泄露其名称。
+pub async fn export_manifest(
+    pool: &SqlitePool,
+    tasks: &[ScheduledTask],
+    names: Option<&[String]>,
+) -> Result<TaskManifest, AppError> {
+    let selected: Vec<&ScheduledTask> = match names {
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                tasks
+                    .iter()
+                    .find(|task| &task.name == name)
+                    .ok_or_else(|| AppError::NotFound(format!("Task not found: {}", name)))
+            })
+            .collect::<Result<_, _>>()?,
+        None => tasks.iter().collect(),
+    };
+
+    let edges = dependency::load_edges(pool).await?;
+    let names_by_id: HashMap<Uuid, &str> = tasks
+        .iter()
+        .map(|task| (task.id, task.name.as_str()))
+        .collect();
+
+    let entries = selected
+        .into_iter()
+        .map(|task| {
+            let depends_on = edges
+                .get(&task.id)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| names_by_id.get(id).map(|name| name.to_string()))
+                .collect();
+            manifest_entry(task, depends_on)
+        })
+        .collect();
+
+    Ok(TaskManifest {
+        version: MANIFEST_VERSION,
+        tasks: entries,
+    })
+}
+
+/// 对比清单与现有任务，生成变更计划
+///
+/// `tasks` 为当前用户创建的任务，清单按名称与其匹配；清单中为空的字段不参与比较。
+/// `deactivate_missing` 时，`tasks` 中未出现在清单里的启用任务将被停用。
+pub async fn plan_import(
+    pool: &SqlitePool,
+    tasks: &[ScheduledTask],
+    manifest: &TaskManifest,
+    deactivate_missing: bool,
+) -> Result<Vec<ManifestChange>, AppError> {
+    let existing = index_by_name(tasks)?;
+    let edges = dependency::load_edges(pool).await?;
+    let names_by_id: HashMap<Uuid, &str> = tasks
+        .iter()
+        .map(|task| (task.id, task.name.as_str()))
+        .collect();
+    let current_dependencies = |task: &ScheduledTask| -> Vec<String> {
+        edges
+            .get(&task.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| names_by_id.get(id).map(|name| name.to_string()))
+            .collect()
+    };
+
+    let mut seen = HashSet::new();
+    for entry in &manifest.tasks {
+        if !seen.insert(entry.name.as_str()) {
+            return Err(AppError::Validation(format!(
+                "Duplicate task name in manifest: {}",
+                entry.name
+            )));
+        }
+    }
+    for entry in &manifest.tasks {
+        for name in &entry.depends_on {
+            if !seen.contains(name.as_str()) && !existing.contains_key(name.as_str()) {
+                return Err(AppError::Validation(format!(
+                    "Task {} depends on unknown task {}",
+                    entry.name, name
+                )));
+            }
+        }
+    }
+    check_cycles(tasks, manifest, &current_dependencies)?;
+
+    let mut changes = Vec::new();
+    for entry in &manifest.tasks {
+        match existing.get(entry.name.as_str()) {
+            Some(task) => {
+                if task.task_type.to_string() != entry.task_type.to_string() {
+                    return Err(AppError::Validation(format!(
+                        "Cannot change task_type of {} from {} to {}",
+                        entry.name, task.task_type, entry.task_type
+                    )));
+                }
+                let diff = entry_diff(&manifest_entry(task, current_dependencies(task)), entry);
+                let action = if diff.is_empty() {
+                    ManifestAction::Unchanged
+                } else {
+                    ManifestAction::Update
+                };
+                changes.push(ManifestChange {
+                    name: entry.name.clone(),
+                    action,
+                    task_id: Some(task.id),
+                    changes: Value::Object(diff),
+                });
+            }
+            None => {
+                if entry.cron_expression.is_none() {
+                    return Err(AppError::Validation(format!(
+                        "Missing cron_expression for new task {}",
+                        entry.name
+                    )));
+                }
+                let fields = match serde_json::to_value(entry) {
+                    Ok(Value::Object(fields)) => fields,
+                    _ => Map::new(),
+                };
+                let diff = fields
+                    .into_iter()
+                    .filter(|(key, _)| key != "name")
+                    .map(|(key, to)| (key, json!({ "from": null, "to": to })))
+                    .collect();
+                changes.push(ManifestChange {
+                    name: entry.name.clone(),
+                    action: ManifestAction::Create,
+                    task_id: None,
+                    changes: Value::Object(diff),
+                });
+            }
+        }
+    }
+
+    if deactivate_missing {
+        for task in tasks {
+            if task.is_active && !seen.contains(task.name.as_str()) {
+                changes.push(ManifestChange {
+                    name: task.name.clone(),
+                    action: ManifestAction::Deactivate,
+                    task_id: Some(task.id),
+                    changes: json!({ "is_active": { "from": true, "to": false } }),
+                });
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// 按变更计划写入任务和依赖关系，返回补全了新任务 ID 的计划
+///
+/// 所有写入在同一个事务中进行，任一步失败时不会留下部分导入的结果。
+pub async fn apply_import(
+    pool: &SqlitePool,
+    tasks: &[ScheduledTask],
+    manifest: &TaskManifest,
+    plan: Vec<ManifestChange>,
+    created_by: Uuid,
+) -> Result<Vec<ManifestChange>, AppError> {
+    let entries: HashMap<&str, &TaskManifestEntry> = manifest
+        .tasks
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry))
+        .collect();
+    let mut ids: HashMap<String, Uuid> = tasks
+        .iter()
+        .map(|task| (task.name.clone(), task.id))
+        .collect();
+
+    let mut transaction: Transaction<'_, sqlx::Sqlite> = pool.begin().await?;
+    let mut applied = Vec::new();
+    for mut change in plan {
+        match change.action {
+            ManifestAction::Create => {
+                let entry = entries[change.name.as_str()];
+                let id = Uuid::new_v4();
+                task::create_task(&mut *transaction, create_request(id, entry, created_by)).await?;
+                ids.insert(entry.name.clone(), id);
+                change.task_id = Some(id);
+            }
+            ManifestAction::Update => {
+                let entry = entries[change.name.as_str()];
+                if let Some(id) = change.task_id {
+                    task::update_task(&mut *transaction, id, update_request(entry)).await?;
+                }
+            }
+            ManifestAction::Deactivate => {
+                if let Some(id) = change.task_id {
+                    task::update_task(
+                        &mut *transaction,
+                        id,
+                        UpdateTaskRequest {
+                            name: None,
+                            description: None,
+                            cron_expression: None,
+                            timezone: None,
+                            one_time: None,
+                            priority: None,
+                            timeout_seconds: None,
+                            max_retries: None,
+                            retry_delay_seconds: None,
+                            parameters: None,
+                            misfire_policy: None,
+                            misfire_max_runs: None,
+                            is_active: Some(false),
+                            status: None,
+                        },
+                    )
+                    .await?;
+                }
+            }
+            ManifestAction::Unchanged => {}
+        }
+        applied.push(change);
+    }
+
+    // 所有任务写入后再同步依赖，前置任务可能是本次新建的任务；
+    // 清单中只有可见的任务，不可见的前置任务保持不变
+    let visible: HashSet<Uuid> = ids.values().copied().collect();
+    for change in &applied {
+        if change.changes.get("depends_on").is_none() {
+            continue;
+        }
+        let (Some(task_id), Some(entry)) = (change.task_id, entries.get(change.name.as_str()))
+        else {
+            continue;
+        };
+        let wanted: HashSet<Uuid> = entry
+            .depends_on
+            .iter()
+            .filter_map(|name| ids.get(name).copied())
+            .collect();
+        let current: HashSet<Uuid> = dependency::list_dependencies(&mut *transaction, task_id)
+            .await?
+            .into_iter()
+            .map(|dependency| dependency.prerequisite_task_id)
+            .filter(|id| visible.contains(id))
+            .collect();
+        for prerequisite in current.difference(&wanted) {
+            dependency::remove_dependency(&mut *transaction, task_id, *prerequisite).await?;
+        }
+        // 依赖是否成环已在 plan_import 中检查
+        for prerequisite in wanted.difference(&current) {
+            dependency::insert_dependency(&mut *transaction, task_id, *prerequisite).await?;
+        }
+    }
+
+    transaction.commit().await?;
+    Ok(applied)
+}
+
+/// 按名称索引任务，名称重复时无法确定导入目标（同一用户的任务名称由唯一索引保证不重复）
+fn index_by_name(tasks: &[ScheduledTask]) -> Result<HashMap<&str, &ScheduledTask>, AppError> {
+    let mut index = HashMap::new();
+    for task in tasks {
+        if index.insert(task.name.as_str(), task).is_some() {
+            return Err(AppError::Validation(format!(
+                "Task name is not unique: {}",
+                task.name
+            )));
+        }
+    }
+    Ok(index)
+}
+
+/// 以清单中的依赖替换现有依赖后检查是否成环
+fn check_cycles(
+    tasks: &[ScheduledTask],
+    manifest: &TaskManifest,
+    current_dependencies: &dyn Fn(&ScheduledTask) -> Vec<String>,
+) -> Result<(), AppError> {
+    let mut graph: HashMap<String, Vec<String>> = tasks
+        .iter()
+        .map(|task| (task.name.clone(), current_dependencies(task)))
+        .collect();
+    for entry in &manifest.tasks {
+        graph.insert(entry.name.clone(), entry.depends_on.clone());
+    }
+
+    // 0: 未访问，1: 访问中，2: 已完成
+    let mut state: HashMap<&str, u8> = HashMap::new();
+    for start in graph.keys() {
+        let mut stack = vec![(start.as_str(), 0)];
+        while let Some((name, index)) = stack.pop() {
+            if index == 0 {
+                match state.get(name) {
+                    Some(2) => continue,
+                    Some(1) => {
+                        return Err(AppError::Validation(format!(
+                            "Dependency cycle involving task {}",
+                            name
+                        )))
+                    }
+                    _ => {
+                        state.insert(name, 1);
+                    }
+                }
+            }
+            match graph.get(name).and_then(|next| next.get(index)) {
+                Some(next) => {
+                    stack.push((name, index + 1));
+                    stack.push((next.as_str(), 0));
+                }
+                None => {
+                    state.insert(name, 2);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 现有任务对应的清单条目
+fn manifest_entry(task: &ScheduledTask, mut depends_on: Vec<String>) -> TaskManifestEntry {
+    depends_on.sort();
+    TaskManifestEntry {
+        name: task.name.clone(),
+        description: task.description.clone(),
+        task_type: task.task_type.clone(),
+        cron_expression: task.cron_expression.clone(),
+        timezone: Some(task.timezone.clone()),
+        one_time: Some(task.one_time),
+        priority: Some(task.priority.clone()),
+        timeout_seconds: task.timeout_seconds.map(|seconds| seconds as i32),
+        max_retries: Some(task.max_retries as i32),
+        retry_delay_seconds: Some(task.retry_delay_seconds as i32),
+        parameters: task.parameters.clone(),
+        misfire_policy: Some(task.misfire_policy.clone()),
+        misfire_max_runs: Some(task.misfire_max_runs as i32),
+        is_active: Some(task.is_active),
+        depends_on,
+    }
+}
+
+/// 比较清单条目与现有任务，忽略清单中未设置的字段；依赖关系按集合比较
+fn entry_diff(current: &TaskManifestEntry, wanted: &TaskManifestEntry) -> Map<String, Value> {
+    let fields = |entry: &TaskManifestEntry| match serde_json::to_value(entry) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    let (before, after) = (fields(current), fields(wanted));
+
+    let mut changes = Map::new();
+    for (key, to) in &after {
+        if key == "name" || key == "depends_on" {
+            continue;
+        }
+        let from = before.get(key).unwrap_or(&Value::Null);
+        if from != to {
+            changes.insert(key.clone(), json!({ "from": from, "to": to }));
+        }
+    }
+
+    let mut depends_on = wanted.depends_on.clone();
+    depends_on.sort();
+    depends_on.dedup();
+    if current.depends_on != depends_on {
+        changes.insert(
+            "depends_on".to_string(),
+            json!({ "from": current.depends_on, "to": depends_on }),
+        );
+    }
+    changes
+}
+
+fn create_request(id: Uuid, entry: &TaskManifestEntry, created_by: Uuid) -> CreateTaskRequest {
+    CreateTaskRequest {
+        id,
+        name: entry.name.clone(),
+        description: entry.description.clone(),
+        task_type: entry.task_type.clone(),
+        cron_expression: entry.cron_expression.clone(),
+        timezone: entry.timezone.clone(),
+        one_time: Some(entry.one_time.unwrap_or(false)),
+        priority: Some(entry.priority.clone().unwrap_or(TaskPriority::Medium)),
+        timeout_seconds: entry.timeout_seconds,
+        max_retries: Some(entry.max_retries.unwrap_or(0)),
+        retry_delay_seconds: Some(entry.retry_delay_seconds.unwrap_or(60)),
+        parameters: entry.parameters.clone(),
+        misfire_policy: Some(entry.misfire_policy.clone().unwrap_or(MisfirePolicy::Skip)),
+        misfire_max_runs: entry.misfire_max_runs,
+        is_active: Some(entry.is_active.unwrap_or(true)),
+        status: TaskStatus::Pending,
+        created_by,
+    }
+}
+
+fn update_request(entry: &TaskManifestEntry) -> UpdateTaskRequest {
+    UpdateTaskRequest {
+        name: None,
+        description: entry.description.clone(),
+        cron_expression: entry.cron_expression.clone(),
+        timezone: entry.timezone.clone(),
+        one_time: entry.one_time,
+        priority: entry.priority.clone(),
+        timeout_seconds: entry.timeout_seconds,
+        max_retries: entry.max_retries,
+        retry_delay_seconds: entry.retry_delay_seconds,
+        parameters: entry.parameters.clone(),
+        misfire_policy: entry.misfire_policy.clone(),
+        misfire_max_runs: entry.misfire_max_runs,
+        is_active: entry.is_active,
+        status: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TaskType;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use std::str::FromStr;
+
+    fn task(name: &str, cron_expression: &str) -> ScheduledTask {
+        ScheduledTask {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            task_type: TaskType::SystemCleanup,
+            cron_expression: Some(cron_expression.to_string()),
+            timezone: "UTC".to_string(),
+            one_time: false,
+            priority: TaskPriority::Medium,
+            timeout_seconds: None,
+            max_retries: 0,
+            retry_delay_seconds: 60,
+            parameters: None,
+            status: TaskStatus::Scheduled,
+            misfire_policy: MisfirePolicy::Skip,
+            misfire_max_runs: 1,
+            is_active: true,
+            created_by: Uuid::nil(),
+            created_at: None,
+            updated_at: None,
+            next_run_at: None,
+            last_run_at: None,
+        }
+    }
+
+    /// 只包含依赖关系的内存数据库，任务本身不落库
+    async fn pool(edges: &[(&ScheduledTask, &ScheduledTask)]) -> SqlitePool {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .foreign_keys(false);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        for (dependent, prerequisite) in edges {
+            dependency::insert_dependency(&pool, dependent.id, prerequisite.id)
+                .await
+                .unwrap();
+        }
+        pool
+    }
+
+    fn manifest(yaml: &str) -> TaskManifest {
+        ManifestFormat::Yaml.deserialize(yaml).unwrap()
+    }
+
+    fn validation_error(result: Result<Vec<ManifestChange>, AppError>) -> String {
+        match result {
+            Err(AppError::Validation(message)) => message,
+            other => panic!("expected validation error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn plan_import_diffs_against_existing_tasks() {
+        let (a, b, c) = (
+            task("a", "0 0 3 * * *"),
+            task("b", "0 0 4 * * *"),
+            task("c", "0 0 5 * * *"),
+        );
+        let pool = pool(&[(&b, &a)]).await;
+        let tasks = [a.clone(), b.clone(), c.clone()];
+        let manifest = manifest(
+            "
+version: 1
+tasks:
+- name: a
+  task_type: SystemCleanup
+  cron_expression: 0 0 3 * * *
+- name: b
+  task_type: SystemCleanup
+  cron_expression: 0 0 6 * * *
+  depends_on: [d, a]
+- name: d
+  task_type: SystemCleanup
+  cron_expression: 0 0 7 * * *
+",
+        );
+
+        let plan = plan_import(&pool, &tasks, &manifest, true).await.unwrap();
+        let actions: Vec<_> = plan
+            .iter()
+            .map(|change| (change.name.as_str(), change.action, change.task_id))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                ("a", ManifestAction::Unchanged, Some(a.id)),
+                ("b", ManifestAction::Update, Some(b.id)),
+                ("d", ManifestAction::Create, None),
+                ("c", ManifestAction::Deactivate, Some(c.id)),
+            ]
+        );
+        assert_eq!(
+            plan[1].changes,
+            json!({
+                "cron_expression": { "from": "0 0 4 * * *", "to": "0 0 6 * * *" },
+                "depends_on": { "from": ["a"], "to": ["a", "d"] },
+            })
+        );
+        assert_eq!(
+            plan[2].changes["cron_expression"],
+            json!({ "from": null, "to": "0 0 7 * * *" })
+        );
+
+        // 不停用缺失任务时 c 不出现在计划中
+        let plan = plan_import(&pool, &tasks, &manifest, false).await.unwrap();
+        assert!(plan.iter().all(|change| change.name != "c"));
+    }
+
+    #[tokio::test]
+    async fn plan_import_rejects_cycles() {
+        let (a, b) = (task("a", "0 0 3 * * *"), task("b", "0 0 4 * * *"));
+        let pool = pool(&[(&b, &a)]).await;
+        let tasks = [a.clone(), b.clone()];
+
+        // 与现有依赖 b -> a 成环
+        let message = validation_error(
+            plan_import(
+                &pool,
+                &tasks,
+                &manifest(
+                    "{version: 1, tasks: [{name: a, task_type: SystemCleanup, depends_on: [b]}]}",
+                ),
+                false,
+            )
+            .await,
+        );
+        assert!(message.starts_with("Dependency cycle"), "{}", message);
+
+        // 清单同时替换 b 的依赖时不再成环
+        let plan = plan_import(
+            &pool,
+            &tasks,
+            &manifest(
+                "
+version: 1
+tasks:
+- {name: a, task_type: SystemCleanup, depends_on: [b]}
+- {name: b, task_type: SystemCleanup}
+",
+            ),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(plan[0].action, ManifestAction::Update);
+
+        // 清单内部成环
+        let message = validation_error(
+            plan_import(
+                &pool,
+                &tasks,
+                &manifest(
+                    "
+version: 1
+tasks:
+- {name: x, task_type: SystemCleanup, cron_expression: '* * * * *', depends_on: [y]}
+- {name: y, task_type: SystemCleanup, cron_expression: '* * * * *', depends_on: [x]}
+",
+                ),
+                false,
+            )
+            .await,
+        );
+        assert!(message.starts_with("Dependency cycle"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn plan_import_validates_entries() {
+        let a = task("a", "0 0 3 * * *");
+        let pool = pool(&[]).await;
+        let tasks = [a];
+
+        let cases = [
+            (
+                "{version: 1, tasks: [{name: a, task_type: SystemCleanup}, {name: a, task_type: SystemCleanup}]}",
+                "Duplicate task name in manifest: a",
+            ),
+            (
+                "{version: 1, tasks: [{name: a, task_type: SystemCleanup, depends_on: [nope]}]}",
+                "Task a depends on unknown task nope",
+            ),
+            (
+                "{version: 1, tasks: [{name: a, task_type: DataBackup}]}",
+                "Cannot change task_type of a from system_cleanup to data_backup",
+            ),
+            (
+                "{version: 1, tasks: [{name: new, task_type: SystemCleanup}]}",
+                "Missing cron_expression for new task new",
+            ),
+        ];
+        for (yaml, expected) in cases {
+            let message =
+                validation_error(plan_import(&pool, &tasks, &manifest(yaml), false).await);
+            assert_eq!(message, expected);
+        }
+    }
+}
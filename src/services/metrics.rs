@@ -1,5 +1,6 @@
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
 use std::net::SocketAddr;
+use tracing::{error, info};
 
 pub fn setup_metrics_recorder() -> PrometheusHandle {
     const EXPONENTIAL_SECONDS: &[f64] = &[
@@ -12,6 +13,11 @@ pub fn setup_metrics_recorder() -> PrometheusHandle {
             EXPONENTIAL_SECONDS,
         )
         .unwrap()
+        .set_buckets_for_metric(
+            Matcher::Full("task_queue_wait_seconds".to_string()),
+            EXPONENTIAL_SECONDS,
+        )
+        .unwrap()
         .install_recorder()
         .unwrap()
 }
@@ -28,10 +34,16 @@ pub async fn serve_metrics(addr: SocketAddr, handle: PrometheusHandle) {
         }),
     );
 
-    axum::serve(
-        tokio::net::TcpListener::bind(&addr).await.unwrap(),
-        app.into_make_service(),
-    )
-    .await
-    .unwrap();
+    // 指标服务不可用时不影响主服务
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(%addr, error = ?e, "Failed to bind metrics exporter");
+            return;
+        }
+    };
+    info!("Metrics exporter listening on {}", addr);
+    if let Err(e) = axum::serve(listener, app.into_make_service()).await {
+        error!(error = ?e, "Metrics exporter stopped");
+    }
 }
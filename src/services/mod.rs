@@ -1,6 +1,11 @@
 pub mod broadcast;
+pub mod cluster;
+pub mod dependency;
 pub mod document;
+pub mod execution;
+pub mod executor;
 pub mod log;
+pub mod manifest;
 pub mod metrics;
 pub mod monitor;
 pub mod monitor_task;
@@ -21,6 +21,12 @@ pub struct SystemStatusBroadcaster {
     channel: Arc<RwLock<broadcast::Sender<SystemStatus>>>,
 }
 
+impl Default for SystemStatusBroadcaster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SystemStatusBroadcaster {
     pub fn new() -> Self {
         let (sender, _) = broadcast::channel(100);
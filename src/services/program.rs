@@ -1,87 +1,112 @@
 use crate::{
     error::AppError,
     models::{
-        CreateProgramRequest, ListProgramQuery, ListProgramResponse, Program,
-        ProgramCompileResponse, ProgramExecution, ProgramStatus, UpdateProgram,
+        CreateProgramRequest, ListProgramQuery, ListProgramResponse, ListProgramRunResponse,
+        ListProgramRunsQuery, Program, ProgramCompileResponse, ProgramEventKind, ProgramExecution,
+        ProgramRun, ProgramRunCommand, ProgramRunStatus, ProgramStatus, UpdateProgram,
     },
-    services::program,
-    utils::python::PythonExecutor,
+    services::executor::truncate,
+    utils::python::{PythonExecutor, PythonOutput, SandboxLimits, StdStream},
 };
 use chrono::{DateTime, Utc};
-use serde_json::Value;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 use sqlx::SqlitePool;
-use tokio::sync::broadcast;
+use std::path::{Path, PathBuf};
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
 use uuid::Uuid;
 
+/// 每次运行的事件通道容量，客户端读取落后超过该数量时丢弃最早的事件
+const EXECUTION_CHANNEL_CAPACITY: usize = 1024;
+
+/// 交互式运行的命令通道容量，写满时客户端的后续消息等待程序读取
+const COMMAND_CHANNEL_CAPACITY: usize = 64;
+
+/// 运行记录中保存的输入、输出的最大字节数
+const RUN_PREVIEW_BYTES: usize = 16 * 1024;
+
+/// 交互式运行中记录的标准输入上限，超出的部分不再记录
+const RUN_INPUT_CAPTURE_BYTES: usize = 1024 * 1024;
+
 pub struct ProgramService {
     pool: SqlitePool,
     python_executor: PythonExecutor,
-    execution_updates: broadcast::Sender<ProgramExecution>,
+    runs_dir: PathBuf, // 截断时保存完整输入输出的目录
 }
 
 impl ProgramService {
-    pub fn new(pool: SqlitePool, python_executor: PythonExecutor) -> Self {
-        let (execution_updates, _) = broadcast::channel(100);
+    pub fn new(
+        pool: SqlitePool,
+        python_executor: PythonExecutor,
+        runs_dir: impl Into<PathBuf>,
+    ) -> Self {
         Self {
             pool,
             python_executor,
-            execution_updates,
+            runs_dir: runs_dir.into(),
         }
     }
     pub async fn compile_program(&self, id: Uuid) -> Result<ProgramCompileResponse, AppError> {
-        let mut program = get_program(&self.pool, id).await?;
+        let program = get_program(&self.pool, id).await?;
 
         // 更新状态为编译中
-        program.status = ProgramStatus::Compiling;
-        let mut update_program = UpdateProgram {
-            name: Some(program.name.clone()),
-            source_code: Some(program.source_code.clone()),
-            status: Some(program.status),
-            is_active: Some(program.is_active),
-            description: program.description.clone(),
-            metadata: program.metadata.clone(),
-        };
-        program::update_program(&self.pool, id, update_program.clone()).await?;
+        set_compile_result(
+            &self.pool,
+            id,
+            &program.source_code,
+            ProgramStatus::Compiling,
+            None,
+        )
+        .await?;
 
         // 执行编译
-        match self.python_executor.compile(&program.source_code).await {
-            Ok(_) => {
-                program.status = ProgramStatus::Compiled;
-                update_program.status = Some(program.status.clone());
-                program::update_program(&self.pool, id, update_program).await?;
-
-                Ok(ProgramCompileResponse {
-                    status: program.status,
-                    time: Utc::now(),
-                    error_file: None,
-                    error_type: None,
-                    error_line: None,
-                    error_message: None,
-                    error_suggestions: None,
-                })
-            }
-            Err(e) => {
-                program.status = ProgramStatus::Failed;
-                update_program.status = Some(program.status.clone());
-                program::update_program(&self.pool, id, update_program.clone()).await?;
-
-                Ok(ProgramCompileResponse {
-                    status: program.status,
-                    time: Utc::now(),
-                    error_file: e.error_file,
-                    error_type: e.error_type,
-                    error_line: e.error_line,
-                    error_message: e.error_message,
-                    error_suggestions: e.error_suggestions,
-                })
-            }
+        let (status, compiled_code, error) =
+            match self.python_executor.compile(&program.source_code).await {
+                Ok(compiled_code) => (ProgramStatus::Compiled, Some(compiled_code), None),
+                Err(e) => (ProgramStatus::Failed, None, Some(e)),
+            };
+        // 编译期间源代码被修改时结果作废，程序保持待编译状态
+        if !set_compile_result(
+            &self.pool,
+            id,
+            &program.source_code,
+            status.clone(),
+            compiled_code,
+        )
+        .await?
+        {
+            return Err(AppError::BadRequest(
+                "Program source changed during compilation".to_string(),
+            ));
         }
+
+        Ok(match error {
+            None => ProgramCompileResponse {
+                status,
+                time: Utc::now(),
+                error_file: None,
+                error_type: None,
+                error_line: None,
+                error_message: None,
+                error_suggestions: None,
+            },
+            Some(e) => ProgramCompileResponse {
+                status,
+                time: Utc::now(),
+                error_file: e.error_file,
+                error_type: e.error_type,
+                error_line: e.error_line,
+                error_message: e.error_message,
+                error_suggestions: e.error_suggestions,
+            },
+        })
     }
 
-    pub async fn run_program(
-        &self,
-        id: Uuid,
-    ) -> Result<broadcast::Receiver<ProgramExecution>, AppError> {
+    /// 以 `user_id` 的身份启动一次运行并记录到 `program_runs`
+    ///
+    /// 接收器只收到本次运行的事件，以 `Exit` 事件结束。
+    pub async fn run_program(&self, id: Uuid, user_id: Uuid) -> Result<ProgramRunHandle, AppError> {
         let program = get_program(&self.pool, id).await?;
 
         if program.status != ProgramStatus::Compiled {
@@ -90,44 +115,152 @@ impl ProgramService {
             ));
         }
 
-        // 创建一个新的接收器
-        let receiver = self.execution_updates.subscribe();
+        // 先创建接收器再启动，避免错过最早的事件
+        let run_id = create_run(&self.pool, &program, user_id).await?;
+        let (updates, receiver) = broadcast::channel(EXECUTION_CHANNEL_CAPACITY);
 
-        // 启动程序执行
-        let executor = self.python_executor.clone();
-        let sender = self.execution_updates.clone();
-        let program_id = program.id;
+        let (input, commands) = mpsc::channel(COMMAND_CHANNEL_CAPACITY);
 
+        let pool = self.pool.clone();
+        let executor = self.python_executor.clone();
+        let runs_dir = self.runs_dir.clone();
+        let mut events = RunEvents::new(updates, program.id, run_id);
         tokio::spawn(async move {
-            let sender_clone = sender.clone();
-
             let result = executor
-                .execute_with_updates(&program.source_code, move |line, output, error| {
-                    let update = ProgramExecution {
-                        id: program_id.clone(),
-                        line: line as i32,
-                        input: None,
-                        output: output.clone(),
-                        error: error.clone(),
-                        timestamp: Utc::now(),
-                    };
-                    let _ = sender_clone.send(update);
+                .execute_with_updates(&program.source_code, commands, |stream, line| {
+                    events.line(stream, line)
                 })
                 .await;
 
-            if let Err(e) = result {
-                let _ = sender.send(ProgramExecution {
-                    id: program_id.clone(),
-                    line: -1,
-                    input: None,
-                    output: None,
-                    error: Some(e.to_string()),
-                    timestamp: Utc::now(),
-                });
+            match &result {
+                Ok(output) => {
+                    if let Some(violation) = output.violation {
+                        let message = violation.describe(executor.limits());
+                        events.emit(ProgramEventKind::Violation, |event| {
+                            event.error = Some(message)
+                        });
+                    }
+                    events.emit(ProgramEventKind::Exit, |event| {
+                        event.exit_code = output.exit_code;
+                        event.duration_ms = Some(output.duration_ms);
+                        if output.killed {
+                            event.error = Some("Program killed".to_string());
+                        }
+                    });
+                }
+                Err(e) => {
+                    events.emit(ProgramEventKind::Error, |event| {
+                        event.error = Some(e.to_string())
+                    });
+                    events.emit(ProgramEventKind::Exit, |_| {});
+                }
+            }
+
+            if let Err(e) = finish_run(
+                &pool,
+                &runs_dir,
+                run_id,
+                &events.input,
+                &result,
+                executor.limits(),
+            )
+            .await
+            {
+                warn!(run_id = %run_id, error = %e, "Failed to record program run");
             }
         });
 
-        Ok(receiver)
+        Ok(ProgramRunHandle {
+            run_id,
+            events: receiver,
+            input,
+        })
+    }
+}
+
+/// 一次交互式运行
+pub struct ProgramRunHandle {
+    pub run_id: Uuid,
+    pub events: broadcast::Receiver<ProgramExecution>, // 本次运行的事件
+    pub input: mpsc::Sender<ProgramRunCommand>,        // 发送端全部关闭时视为 EOF
+}
+
+/// 一次运行产生的事件，按产生顺序编号
+struct RunEvents {
+    sender: broadcast::Sender<ProgramExecution>,
+    program_id: Uuid,
+    run_id: Uuid,
+    sequence: u64,
+    input: String, // 已写入的标准输入
+    stdin_lines: i32,
+    stdout_lines: i32,
+    stderr_lines: i32,
+}
+
+impl RunEvents {
+    fn new(sender: broadcast::Sender<ProgramExecution>, program_id: Uuid, run_id: Uuid) -> Self {
+        Self {
+            sender,
+            program_id,
+            run_id,
+            sequence: 0,
+            input: String::new(),
+            stdin_lines: 0,
+            stdout_lines: 0,
+            stderr_lines: 0,
+        }
+    }
+
+    fn line(&mut self, stream: StdStream, text: String) {
+        match stream {
+            StdStream::Stdin => {
+                if self.input.len() < RUN_INPUT_CAPTURE_BYTES {
+                    self.input.push_str(&text);
+                }
+                self.stdin_lines += 1;
+                let line = self.stdin_lines;
+                self.emit(ProgramEventKind::Input, |event| {
+                    event.line = line;
+                    event.input = Some(text);
+                });
+            }
+            StdStream::Stdout => {
+                self.stdout_lines += 1;
+                let line = self.stdout_lines;
+                self.emit(ProgramEventKind::Stdout, |event| {
+                    event.line = line;
+                    event.output = Some(text);
+                });
+            }
+            StdStream::Stderr => {
+                self.stderr_lines += 1;
+                let line = self.stderr_lines;
+                self.emit(ProgramEventKind::Stderr, |event| {
+                    event.line = line;
+                    event.error = Some(text);
+                });
+            }
+        }
+    }
+
+    fn emit(&mut self, kind: ProgramEventKind, fill: impl FnOnce(&mut ProgramExecution)) {
+        let mut event = ProgramExecution {
+            id: self.program_id,
+            run_id: self.run_id,
+            sequence: self.sequence,
+            kind,
+            line: 0,
+            input: None,
+            output: None,
+            error: None,
+            exit_code: None,
+            duration_ms: None,
+            timestamp: Utc::now(),
+        };
+        fill(&mut event);
+        self.sequence += 1;
+        // 没有订阅者时发送失败，忽略
+        let _ = self.sender.send(event);
     }
 }
 
@@ -135,7 +268,8 @@ pub async fn create_program(
     pool: &SqlitePool,
     program: CreateProgramRequest,
 ) -> Result<(), AppError> {
-    let status = program.status.to_string();
+    // 新程序总是待编译，不接受客户端指定的状态
+    let status = ProgramStatus::Pending.to_string();
     sqlx::query_as!(
         CreateProgramRequest,
         r#"
@@ -175,8 +309,9 @@ pub async fn get_program(pool: &SqlitePool, id: Uuid) -> Result<Program, AppErro
         FROM programs WHERE id = ?"#,
         id
     )
-    .fetch_one(pool)
-    .await?;
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Program not found".to_string()))?;
     Ok(program)
 }
 
@@ -209,18 +344,27 @@ pub async fn list_programs(
     Ok(programs)
 }
 
+/// 修改程序；源代码有变化时清空编译结果，程序回到待编译状态
 pub async fn update_program(
     pool: &SqlitePool,
     id: Uuid,
     program: UpdateProgram,
 ) -> Result<(), AppError> {
+    let pending = ProgramStatus::Pending.to_string();
+    // SET 中的列引用的都是修改前的值
     sqlx::query!(
         r#"UPDATE programs SET
-            name = COALESCE(?, name), source_code = COALESCE(?, source_code), status = COALESCE(?, status), is_active = COALESCE(?, is_active), description = COALESCE(?, description), metadata = COALESCE(?, metadata)
+            status = CASE WHEN ? IS NOT NULL AND ? != source_code THEN ? ELSE status END,
+            compiled_code = CASE WHEN ? IS NOT NULL AND ? != source_code THEN NULL ELSE compiled_code END,
+            name = COALESCE(?, name), source_code = COALESCE(?, source_code), is_active = COALESCE(?, is_active), description = COALESCE(?, description), metadata = COALESCE(?, metadata)
         WHERE id = ?"#,
+        program.source_code,
+        program.source_code,
+        pending,
+        program.source_code,
+        program.source_code,
         program.name,
         program.source_code,
-        program.status,
         program.is_active,
         program.description,
         program.metadata,
@@ -231,7 +375,36 @@ pub async fn update_program(
     Ok(())
 }
 
+/// 保存 `source_code` 的编译状态和结果，源代码已被修改时不保存并返回 false
+async fn set_compile_result(
+    pool: &SqlitePool,
+    id: Uuid,
+    source_code: &str,
+    status: ProgramStatus,
+    compiled_code: Option<String>,
+) -> Result<bool, AppError> {
+    let status = status.to_string();
+    let result = sqlx::query!(
+        "UPDATE programs SET status = ?, compiled_code = ? WHERE id = ? AND source_code = ?",
+        status,
+        compiled_code,
+        id,
+        source_code
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
 pub async fn delete_program(pool: &SqlitePool, id: Uuid) -> Result<(), AppError> {
+    let artifacts = sqlx::query_scalar!(
+        "SELECT artifact_path FROM program_runs WHERE program_id = ? AND artifact_path IS NOT NULL",
+        id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // 运行记录随程序级联删除
     let result = sqlx::query!(r#"DELETE FROM programs WHERE id = ?"#, id)
         .execute(pool)
         .await?;
@@ -240,5 +413,347 @@ pub async fn delete_program(pool: &SqlitePool, id: Uuid) -> Result<(), AppError>
         return Err(AppError::NotFound("Program not found".to_string()));
     }
 
+    for path in artifacts.into_iter().flatten() {
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!(path = %path, error = %e, "Failed to remove program run artifact");
+        }
+    }
+
     Ok(())
 }
+
+/// 记录一次运行的开始，保存运行者和所运行的源码
+pub async fn create_run(
+    pool: &SqlitePool,
+    program: &Program,
+    user_id: Uuid,
+) -> Result<Uuid, AppError> {
+    let id = Uuid::new_v4();
+    let source_hash = hex::encode(Sha256::digest(program.source_code.as_bytes()));
+    let status = ProgramRunStatus::Running.to_string();
+    sqlx::query!(
+        r#"INSERT INTO program_runs (id, program_id, user_id, source_hash, source_code, status)
+        VALUES (?, ?, ?, ?, ?, ?)"#,
+        id,
+        program.id,
+        user_id,
+        source_hash,
+        program.source_code,
+        status
+    )
+    .execute(pool)
+    .await?;
+    Ok(id)
+}
+
+/// 记录运行结果
+///
+/// 输入和输出超过 `RUN_PREVIEW_BYTES` 时截断保存，完整内容写入 `runs_dir` 下的文件。
+pub async fn finish_run(
+    pool: &SqlitePool,
+    runs_dir: &Path,
+    id: Uuid,
+    input: &str,
+    result: &Result<PythonOutput, AppError>,
+    limits: &SandboxLimits,
+) -> Result<(), AppError> {
+    let (status, error) = match result {
+        Ok(output) if output.killed => (ProgramRunStatus::Killed, None),
+        Ok(output) => match output.violation {
+            Some(violation) => (
+                ProgramRunStatus::LimitExceeded,
+                Some(violation.describe(limits)),
+            ),
+            None if output.success() => (ProgramRunStatus::Completed, None),
+            None => (ProgramRunStatus::Failed, None),
+        },
+        Err(e) => (ProgramRunStatus::Error, Some(e.to_string())),
+    };
+    let output = result.as_ref().ok();
+
+    let (stdout, stderr) = output.map_or(("", ""), |output| {
+        (output.stdout.as_str(), output.stderr.as_str())
+    });
+    let (input_preview, input_truncated) = truncate(input, RUN_PREVIEW_BYTES);
+    let (stdout_preview, stdout_truncated) = truncate(stdout, RUN_PREVIEW_BYTES);
+    let (stderr_preview, stderr_truncated) = truncate(stderr, RUN_PREVIEW_BYTES);
+    let output_truncated = input_truncated || stdout_truncated || stderr_truncated;
+
+    let artifact_path = if output_truncated {
+        Some(write_run_artifact(runs_dir, id, input, stdout, stderr).await?)
+    } else {
+        None
+    };
+
+    let status = status.to_string();
+    let input_preview = Some(input_preview).filter(|input| !input.is_empty());
+    let stdout_preview = output.map(|_| stdout_preview);
+    let stderr_preview = output.map(|_| stderr_preview);
+    let exit_code = output.and_then(|output| output.exit_code);
+    let duration_ms = output.map(|output| output.duration_ms);
+    let usage = output.map(|output| output.usage);
+    let cpu_user_ms = usage.map(|usage| usage.cpu_user_ms);
+    let cpu_system_ms = usage.map(|usage| usage.cpu_system_ms);
+    let max_rss_kb = usage.map(|usage| usage.max_rss_kb);
+    sqlx::query!(
+        r#"UPDATE program_runs SET
+            input = ?, status = ?, exit_code = ?, error = ?, duration_ms = ?,
+            cpu_user_ms = ?, cpu_system_ms = ?, max_rss_kb = ?,
+            stdout = ?, stderr = ?, output_truncated = ?, artifact_path = ?,
+            finished_at = CURRENT_TIMESTAMP
+        WHERE id = ?"#,
+        input_preview,
+        status,
+        exit_code,
+        error,
+        duration_ms,
+        cpu_user_ms,
+        cpu_system_ms,
+        max_rss_kb,
+        stdout_preview,
+        stderr_preview,
+        output_truncated,
+        artifact_path,
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// 运行未正常结束（所属任务超时或被取消）时标记为已取消，已记录结果的运行不受影响
+pub async fn cancel_run(pool: &SqlitePool, id: Uuid) -> Result<(), AppError> {
+    let running = ProgramRunStatus::Running.to_string();
+    let canceled = ProgramRunStatus::Canceled.to_string();
+    sqlx::query!(
+        r#"UPDATE program_runs SET status = ?, error = 'Run canceled', finished_at = CURRENT_TIMESTAMP
+        WHERE id = ? AND status = ?"#,
+        canceled,
+        id,
+        running
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// 将完整的输入输出写入 `<runs_dir>/<运行 ID>.json`，返回文件路径
+async fn write_run_artifact(
+    runs_dir: &Path,
+    id: Uuid,
+    input: &str,
+    stdout: &str,
+    stderr: &str,
+) -> Result<String, AppError> {
+    tokio::fs::create_dir_all(runs_dir).await?;
+    let path = runs_dir.join(format!("{}.json", id));
+    let content = json!({ "input": input, "stdout": stdout, "stderr": stderr });
+    tokio::fs::write(&path, content.to_string()).await?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// 删除早于 `before` 且已结束的运行记录，每次最多删除 `limit` 条
+///
+/// 完整输入输出文件由清理任务按修改时间另行删除。
+pub async fn delete_runs_before(
+    pool: &SqlitePool,
+    before: DateTime<Utc>,
+    limit: i64,
+) -> Result<u64, AppError> {
+    let running = ProgramRunStatus::Running.to_string();
+    let result = sqlx::query!(
+        "DELETE FROM program_runs WHERE id IN (
+            SELECT id FROM program_runs
+            WHERE status != ? AND datetime(started_at) < datetime(?)
+            LIMIT ?
+        )",
+        running,
+        before,
+        limit
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn count_runs_before(pool: &SqlitePool, before: DateTime<Utc>) -> Result<i64, AppError> {
+    let running = ProgramRunStatus::Running.to_string();
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM program_runs
+        WHERE status != ? AND datetime(started_at) < datetime(?)"#,
+        running,
+        before
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+pub async fn list_runs(
+    pool: &SqlitePool,
+    program_id: Uuid,
+    query: ListProgramRunsQuery,
+) -> Result<Vec<ListProgramRunResponse>, AppError> {
+    let limit = query.size.unwrap_or(20).clamp(1, 100);
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1) * limit;
+
+    let runs = sqlx::query_as!(
+        ListProgramRunResponse,
+        r#"SELECT
+            id as "id: Uuid", user_id as "user_id: Uuid", source_hash, status as "status: String", exit_code, error, duration_ms, cpu_user_ms, cpu_system_ms, max_rss_kb, output_truncated, started_at as "started_at: DateTime<Utc>", finished_at as "finished_at: DateTime<Utc>"
+        FROM program_runs
+        WHERE program_id = ?
+        AND (? IS NULL OR status = ?)
+        AND (? IS NULL OR user_id = ?)
+        ORDER BY started_at DESC, rowid DESC
+        LIMIT ? OFFSET ?"#,
+        program_id,
+        query.status,
+        query.status,
+        query.user_id,
+        query.user_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(runs)
+}
+
+pub async fn get_run(
+    pool: &SqlitePool,
+    program_id: Uuid,
+    id: Uuid,
+) -> Result<ProgramRun, AppError> {
+    sqlx::query_as!(
+        ProgramRun,
+        r#"SELECT
+            id as "id: Uuid", program_id as "program_id: Uuid", user_id as "user_id: Uuid", source_hash, source_code, input, status as "status: String", exit_code, error, duration_ms, cpu_user_ms, cpu_system_ms, max_rss_kb, stdout, stderr, output_truncated, artifact_path, started_at as "started_at: DateTime<Utc>", finished_at as "finished_at: DateTime<Utc>"
+        FROM program_runs WHERE id = ? AND program_id = ?"#,
+        id,
+        program_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Program run not found".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use std::str::FromStr;
+
+    /// 写入一个已编译的程序；不检查外键
+    async fn compiled_program() -> (SqlitePool, Uuid) {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .foreign_keys(false);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        let id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO programs (id, user_id, name, source_code, compiled_code, status)
+             VALUES (?, ?, 'report', 'print(1)', 'print(1)', 'compiled')",
+        )
+        .bind(id)
+        .bind(Uuid::new_v4())
+        .execute(&pool)
+        .await
+        .unwrap();
+        (pool, id)
+    }
+
+    fn edit(source_code: Option<&str>) -> UpdateProgram {
+        UpdateProgram {
+            name: Some("renamed".to_string()),
+            source_code: source_code.map(str::to_string),
+            is_active: None,
+            description: None,
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn editing_source_requires_recompiling() {
+        let (pool, id) = compiled_program().await;
+
+        update_program(&pool, id, edit(None)).await.unwrap();
+        update_program(&pool, id, edit(Some("print(1)")))
+            .await
+            .unwrap();
+        let program = get_program(&pool, id).await.unwrap();
+        assert_eq!(program.status, ProgramStatus::Compiled);
+        assert_eq!(program.compiled_code.as_deref(), Some("print(1)"));
+
+        update_program(&pool, id, edit(Some("print(2)")))
+            .await
+            .unwrap();
+        let program = get_program(&pool, id).await.unwrap();
+        assert_eq!(program.name, "renamed");
+        assert_eq!(program.source_code, "print(2)");
+        assert_eq!(program.status, ProgramStatus::Pending);
+        assert_eq!(program.compiled_code, None);
+    }
+
+    #[tokio::test]
+    async fn compile_result_is_dropped_when_source_changed() {
+        let (pool, id) = compiled_program().await;
+        update_program(&pool, id, edit(Some("print(2)")))
+            .await
+            .unwrap();
+
+        let saved = set_compile_result(
+            &pool,
+            id,
+            "print(1)",
+            ProgramStatus::Compiled,
+            Some("print(1)".to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(!saved);
+        let program = get_program(&pool, id).await.unwrap();
+        assert_eq!(program.status, ProgramStatus::Pending);
+        assert_eq!(program.compiled_code, None);
+    }
+
+    #[test]
+    fn run_events_are_numbered_in_order_per_stream() {
+        let (sender, mut receiver) = broadcast::channel(16);
+        let mut events = RunEvents::new(sender, Uuid::new_v4(), Uuid::new_v4());
+        events.line(StdStream::Stdout, "a".to_string());
+        events.line(StdStream::Stdin, "x\n".to_string());
+        events.line(StdStream::Stderr, "oops".to_string());
+        events.line(StdStream::Stdout, "b".to_string());
+        events.emit(ProgramEventKind::Exit, |event| event.exit_code = Some(0));
+
+        let received: Vec<_> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        let sequences: Vec<_> = received.iter().map(|event| event.sequence).collect();
+        assert_eq!(sequences, [0, 1, 2, 3, 4]);
+        let kinds: Vec<_> = received
+            .iter()
+            .map(|event| (event.kind, event.line))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (ProgramEventKind::Stdout, 1),
+                (ProgramEventKind::Input, 1),
+                (ProgramEventKind::Stderr, 1),
+                (ProgramEventKind::Stdout, 2),
+                (ProgramEventKind::Exit, 0),
+            ]
+        );
+        assert_eq!(received[3].output.as_deref(), Some("b"));
+        assert_eq!(events.input, "x\n");
+    }
+}
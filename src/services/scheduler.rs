@@ -1,70 +1,516 @@
 use crate::{
     error::AppError,
-    models::{ScheduledTask, TaskType},
+    models::{MisfirePolicy, ScheduledTask, TaskStatus},
+    services::{
+        cluster::Cluster,
+        dependency,
+        executor::{self, ExecutorRegistry},
+        task,
+    },
+    utils::cron::{missed_fires, next_fire, parse_cron, parse_timezone},
 };
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use futures::future::BoxFuture;
 use serde_json::Value;
 use sqlx::SqlitePool;
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
 use tokio::sync::Mutex;
 use tokio_cron_scheduler::{Job, JobScheduler};
+use tracing::{error, info, warn};
 use uuid::Uuid;
+
+/// 单个任务一次最多补跑的次数，`misfire_max_runs` 超过时按此上限处理
+const MAX_CATCH_UP_RUNS: usize = 1000;
+
 pub struct Scheduler {
     scheduler: JobScheduler,
     pool: SqlitePool,
+    registry: Arc<ExecutorRegistry>,
+    cluster: Arc<Cluster>,
+    jobs: Arc<StdMutex<HashMap<Uuid, Uuid>>>, // 任务 ID -> 调度器 Job ID
 }
 
 impl Scheduler {
-    pub async fn new(pool: SqlitePool) -> Result<Arc<Mutex<Self>>, AppError> {
+    pub async fn new(
+        pool: SqlitePool,
+        registry: ExecutorRegistry,
+        cluster: Arc<Cluster>,
+    ) -> Result<Arc<Mutex<Self>>, AppError> {
         let scheduler = JobScheduler::new().await?;
-        Ok(Arc::new(Mutex::new(Self { scheduler, pool })))
+        Ok(Arc::new(Mutex::new(Self {
+            scheduler,
+            pool,
+            registry: Arc::new(registry),
+            cluster,
+            jobs: Arc::new(StdMutex::new(HashMap::new())),
+        })))
     }
 
     pub async fn start(&mut self) -> Result<(), AppError> {
         // 加载所有活跃的任务
-        let tasks = sqlx::query_as!(
-            ScheduledTask,
-            r#"SELECT
-                id as 'id: Uuid', name, description, one_time, retry_delay_seconds, timeout_seconds, cron_expression, task_type as 'task_type: String', parameters as 'parameters: Value', priority as "priority: String", status as "status: String", max_retries, is_active, created_by as "created_by: Uuid", created_at as 'created_at: DateTime<Utc>', updated_at as 'updated_at: DateTime<Utc>', next_run_at as 'next_run_at: DateTime<Utc>', last_run_at as 'last_run_at: DateTime<Utc>'
-            FROM tasks WHERE is_active = 1"#,
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        let tasks = active_tasks(&self.pool).await?;
+
+        // 所有节点都维护调度，只有主节点触发运行和补偿；之后接任主节点时同样补偿
+        let is_leader = self.cluster.elect().await;
+        let (pool, registry, cluster) = (
+            self.pool.clone(),
+            self.registry.clone(),
+            self.cluster.clone(),
+        );
+        self.cluster.spawn_election(move || {
+            tokio::spawn(catch_up_all(
+                pool.clone(),
+                registry.clone(),
+                cluster.clone(),
+            ));
+        });
 
-        // 为每个任务创建调度
+        // 为每个任务创建调度，单个任务失败不影响其他任务
+        let now = Utc::now();
         for task in tasks {
-            self.add_task(task).await?;
+            let task_id = task.id;
+            if is_leader {
+                if let Err(e) = catch_up(&self.pool, &self.registry, &self.cluster, &task, now) {
+                    error!(task_id = %task_id, error = ?e, "Failed to catch up missed runs");
+                }
+            }
+            if let Err(e) = self.add_task(task).await {
+                error!(task_id = %task_id, error = ?e, "Failed to schedule task");
+            }
         }
 
         self.scheduler.start().await?;
         Ok(())
     }
 
+    /// 添加或重新调度任务，已存在的 Job 会先被移除
+    ///
+    /// 未激活、已暂停、已取消或没有 cron 表达式的任务只会被移除。
     pub async fn add_task(&mut self, task: ScheduledTask) -> Result<(), AppError> {
+        self.remove_task(task.id).await?;
+        if !is_schedulable(&task) {
+            return task::set_next_run_at(&self.pool, task.id, None).await;
+        }
+
+        let schedule = parse_cron(task.cron_expression.as_deref().unwrap_or_default())
+            .map_err(|e| AppError::Validation(e.message))?;
+        let timezone = parse_timezone(&task.timezone)?;
+        let task_id = task.id;
+
+        let job = TaskJob {
+            scheduler: self.scheduler.clone(),
+            jobs: self.jobs.clone(),
+            pool: self.pool.clone(),
+            registry: self.registry.clone(),
+            cluster: self.cluster.clone(),
+            task,
+            schedule,
+            timezone,
+        };
+        let next_run_at = job.schedule_next(None).await?;
+        task::set_next_run_at(&self.pool, task_id, next_run_at).await
+    }
+
+    /// 移除任务对应的调度 Job
+    pub async fn remove_task(&mut self, task_id: Uuid) -> Result<(), AppError> {
+        let job_id = self.jobs.lock().unwrap().remove(&task_id);
+        if let Some(job_id) = job_id {
+            self.scheduler.remove(&job_id).await?;
+        }
+        Ok(())
+    }
+
+    /// 立即在后台执行一次任务
+    pub async fn run_task(&self, task_id: Uuid) -> Result<(), AppError> {
+        let task = task::get_task(&self.pool, task_id).await?;
+        if self.registry.get(&task.task_type).is_none() {
+            return Err(AppError::BadRequest(format!(
+                "No handler registered for task type {}",
+                task.task_type
+            )));
+        }
+
         let pool = self.pool.clone();
-        let cron_expression = task.clone().cron_expression.unwrap_or_default();
-        let job = Job::new_async(cron_expression.as_str(), move |_uuid, _l| {
-            let _pool = pool.clone();
-            let task = task.clone();
-            Box::pin(async move {
-                match task.task_type {
-                    TaskType::EmailNotification => {
-                        // 处理邮件通知任务
-                    }
-                    TaskType::DataBackup => {
-                        // 处理数据备份任务
+        let registry = self.registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = executor::execute_task(&pool, &registry, task).await {
+                error!(task_id = %task_id, error = ?e, "Failed to record task execution");
+            }
+        });
+        Ok(())
+    }
+
+    /// 暂停任务调度，不影响正在进行的执行
+    pub async fn pause_task(&mut self, task_id: Uuid) -> Result<(), AppError> {
+        let task = task::get_task(&self.pool, task_id).await?;
+        if matches!(task.status, TaskStatus::Paused | TaskStatus::Canceled) {
+            return Err(AppError::BadRequest(format!(
+                "Task is already {}",
+                task.status
+            )));
+        }
+
+        task::update_task_status(&self.pool, task_id, TaskStatus::Paused).await?;
+        self.remove_task(task_id).await
+    }
+
+    /// 恢复已暂停或已取消的任务
+    pub async fn resume_task(&mut self, task_id: Uuid) -> Result<(), AppError> {
+        let mut task = task::get_task(&self.pool, task_id).await?;
+        if !matches!(task.status, TaskStatus::Paused | TaskStatus::Canceled) {
+            return Err(AppError::BadRequest(
+                "Only paused or canceled tasks can be resumed".to_string(),
+            ));
+        }
+
+        task::update_task_status(&self.pool, task_id, TaskStatus::Scheduled).await?;
+        task.status = TaskStatus::Scheduled;
+        self.add_task(task).await
+    }
+
+    /// 取消任务：中止正在进行的执行并停止调度
+    pub async fn cancel_task(&mut self, task_id: Uuid) -> Result<Vec<Uuid>, AppError> {
+        task::get_task(&self.pool, task_id).await?;
+        task::update_task_status(&self.pool, task_id, TaskStatus::Canceled).await?;
+        self.remove_task(task_id).await?;
+
+        Ok(self.registry.cancel(task_id))
+    }
+}
+
+/// 单个任务的调度
+///
+/// `tokio_cron_scheduler` 的 cron Job 只支持 UTC，因此每次只安排一个一次性 Job，
+/// 触发时按任务时区计算并安排下一次运行。
+#[derive(Clone)]
+struct TaskJob {
+    scheduler: JobScheduler,
+    jobs: Arc<StdMutex<HashMap<Uuid, Uuid>>>,
+    pool: SqlitePool,
+    registry: Arc<ExecutorRegistry>,
+    cluster: Arc<Cluster>,
+    task: ScheduledTask,
+    schedule: Schedule,
+    timezone: Tz,
+}
+
+impl TaskJob {
+    /// 安排下一次运行，返回下次运行时间
+    ///
+    /// `previous` 为当前触发的 Job，若任务已被移除或重新调度则不再安排。
+    async fn schedule_next(
+        &self,
+        previous: Option<Uuid>,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let now = Utc::now();
+        let Some(next_run_at) = next_fire(&self.schedule, self.timezone, now) else {
+            return Ok(None);
+        };
+        // 调度器按整秒计时，用整秒时间戳相减，避免提前触发
+        let delay = Duration::from_secs((next_run_at.timestamp() - now.timestamp()).max(0) as u64);
+
+        let this = self.clone();
+        let job = Job::new_one_shot_async(delay, move |job_id, _| {
+            this.clone().fire(job_id, next_run_at)
+        })?;
+        let job_id = job.guid();
+
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if previous.is_some() && jobs.get(&self.task.id) != previous.as_ref() {
+                return Ok(None);
+            }
+            jobs.insert(self.task.id, job_id);
+        }
+        self.scheduler.add(job).await?;
+
+        Ok(Some(next_run_at))
+    }
+
+    /// Job 触发：先安排下一次运行，再由抢占到本次运行的主节点执行任务；一次性任务执行前停用
+    fn fire(self, job_id: Uuid, scheduled_at: DateTime<Utc>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let is_current = self.jobs.lock().unwrap().get(&self.task.id) == Some(&job_id);
+            if !is_current {
+                return;
+            }
+
+            // 一次性任务只触发一次，不再安排下一次运行
+            let next_run_at = if self.task.one_time {
+                self.jobs.lock().unwrap().remove(&self.task.id);
+                Ok(None)
+            } else {
+                self.schedule_next(Some(job_id)).await
+            };
+            match next_run_at {
+                Ok(next_run_at) => {
+                    if let Err(e) =
+                        task::set_next_run_at(&self.pool, self.task.id, next_run_at).await
+                    {
+                        error!(task_id = %self.task.id, error = ?e, "Failed to record next run time");
                     }
-                    TaskType::SystemCleanup => {
-                        // 处理系统清理任务
+                }
+                Err(e) => {
+                    error!(task_id = %self.task.id, error = ?e, "Failed to schedule next run");
+                }
+            }
+
+            if !self.cluster.is_leader() {
+                return;
+            }
+            match self.cluster.claim_run(self.task.id, scheduled_at).await {
+                Ok(true) => {
+                    // 先停用再执行，重启后不会再次调度
+                    if self.task.one_time {
+                        if let Err(e) = task::deactivate_task(&self.pool, self.task.id).await {
+                            error!(task_id = %self.task.id, error = ?e, "Failed to deactivate one-time task");
+                        }
                     }
-                    TaskType::Custom(ref _name) => {
-                        // 处理自定义任务
+                    run_with_dependents(self.pool, self.registry, self.cluster, self.task).await
+                }
+                Ok(false) => {
+                    info!(task_id = %self.task.id, %scheduled_at, "Run already claimed by another node");
+                }
+                Err(e) => {
+                    error!(task_id = %self.task.id, error = ?e, "Failed to claim scheduled run");
+                }
+            }
+        })
+    }
+}
+
+async fn active_tasks(pool: &SqlitePool) -> Result<Vec<ScheduledTask>, AppError> {
+    let tasks = sqlx::query_as!(
+        ScheduledTask,
+        r#"SELECT
+            id as 'id: Uuid', name, description, one_time, retry_delay_seconds, timeout_seconds, cron_expression, timezone, task_type as 'task_type: String', parameters as 'parameters: Value', priority as "priority: String", status as "status: String", misfire_policy as "misfire_policy: String", misfire_max_runs, max_retries, is_active, created_by as "created_by: Uuid", created_at as 'created_at: DateTime<Utc>', updated_at as 'updated_at: DateTime<Utc>', next_run_at as 'next_run_at: DateTime<Utc>', last_run_at as 'last_run_at: DateTime<Utc>'
+        FROM tasks WHERE is_active = 1"#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(tasks)
+}
+
+/// 接任主节点后补跑所有任务错过的运行，包括前主节点失效到接任之间错过的运行
+async fn catch_up_all(pool: SqlitePool, registry: Arc<ExecutorRegistry>, cluster: Arc<Cluster>) {
+    let tasks = match active_tasks(&pool).await {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            error!(error = ?e, "Failed to load tasks for catch-up");
+            return;
+        }
+    };
+    let now = Utc::now();
+    for task in tasks {
+        if let Err(e) = catch_up(&pool, &registry, &cluster, &task, now) {
+            error!(task_id = %task.id, error = ?e, "Failed to catch up missed runs");
+        }
+    }
+}
+
+/// 按任务的补偿策略补跑错过的运行
+///
+/// 错过的运行根据 `last_run_at` 与 cron 表达式计算，补偿执行按原定时间依次进行，
+/// 不检查前置任务，也不触发后续任务。
+fn catch_up(
+    pool: &SqlitePool,
+    registry: &Arc<ExecutorRegistry>,
+    cluster: &Arc<Cluster>,
+    task: &ScheduledTask,
+    now: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let keep = match task.misfire_policy {
+        MisfirePolicy::Skip => return Ok(()),
+        MisfirePolicy::RunOnce => 1,
+        MisfirePolicy::RunAll => (task.misfire_max_runs.max(0) as usize).min(MAX_CATCH_UP_RUNS),
+    };
+    let Some(last_run_at) = task.last_run_at else {
+        return Ok(());
+    };
+    if !is_schedulable(task) {
+        return Ok(());
+    }
+
+    let schedule = parse_cron(task.cron_expression.as_deref().unwrap_or_default())
+        .map_err(|e| AppError::Validation(e.message))?;
+    let timezone = parse_timezone(&task.timezone)?;
+
+    let pool = pool.clone();
+    let registry = registry.clone();
+    let cluster = cluster.clone();
+    let task = task.clone();
+    tokio::spawn(async move {
+        let (runs, omitted) = missed_fires(&schedule, timezone, last_run_at, now, keep);
+        if task.misfire_policy == MisfirePolicy::RunAll && omitted {
+            warn!(
+                task_id = %task.id,
+                "Missed runs exceed the catch-up limit, running the latest {}",
+                runs.len()
+            );
+        }
+
+        for scheduled_at in runs {
+            if !matches!(cluster.claim_run(task.id, scheduled_at).await, Ok(true)) {
+                continue;
+            }
+            info!(task_id = %task.id, %scheduled_at, "Catching up missed run");
+            if let Err(e) =
+                executor::execute_catch_up(&pool, &registry, task.clone(), scheduled_at).await
+            {
+                error!(task_id = %task.id, error = ?e, "Failed to record task execution");
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn is_schedulable(task: &ScheduledTask) -> bool {
+    task.is_active
+        && task.cron_expression.is_some()
+        && !matches!(task.status, TaskStatus::Paused | TaskStatus::Canceled)
+}
+
+/// 执行任务：前置任务未在本轮完成时跳过，成功后触发已就绪的后续任务
+///
+/// 后续任务的每一轮运行需先通过 `Cluster` 抢占，避免多个前置任务同时完成时重复执行。
+pub fn run_with_dependents(
+    pool: SqlitePool,
+    registry: Arc<ExecutorRegistry>,
+    cluster: Arc<Cluster>,
+    task: ScheduledTask,
+) -> BoxFuture<'static, ()> {
+    Box::pin(async move {
+        let task_id = task.id;
+        match dependency::prerequisites_satisfied(&pool, task_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!(task_id = %task_id, "Prerequisites not completed, skipping run");
+                return;
+            }
+            Err(e) => {
+                error!(task_id = %task_id, error = ?e, "Failed to check task prerequisites");
+                return;
+            }
+        }
+
+        let result = match executor::execute_task(&pool, &registry, task).await {
+            Ok(result) => result,
+            Err(e) => {
+                error!(task_id = %task_id, error = ?e, "Failed to record task execution");
+                return;
+            }
+        };
+        if result.status != TaskStatus::Completed {
+            return;
+        }
+
+        match dependency::ready_dependents(&pool, task_id).await {
+            Ok(dependents) => {
+                for (dependent, round) in dependents {
+                    match cluster
+                        .claim_dependent_run(dependent.id, round, result.execution_id)
+                        .await
+                    {
+                        Ok(true) => {
+                            tokio::spawn(run_with_dependents(
+                                pool.clone(),
+                                registry.clone(),
+                                cluster.clone(),
+                                dependent,
+                            ));
+                        }
+                        Ok(false) => {
+                            info!(task_id = %dependent.id, "Dependent run already claimed");
+                        }
+                        Err(e) => {
+                            error!(task_id = %dependent.id, error = ?e, "Failed to claim dependent run");
+                        }
                     }
                 }
-            })
-        })?;
+            }
+            Err(e) => {
+                error!(task_id = %task_id, error = ?e, "Failed to load dependent tasks");
+            }
+        }
+    })
+}
 
-        self.scheduler.add(job).await?;
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::TaskType,
+        services::{
+            cluster::MemoryLeaseStore,
+            executor::{TaskContext, TaskHandler},
+        },
+    };
+    use axum::async_trait;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use std::{
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    struct Counter(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl TaskHandler for Counter {
+        async fn execute(&self, _: &TaskContext) -> Result<Value, AppError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn one_time_task_fires_once() {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .foreign_keys(false);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+
+        // 每秒触发的一次性任务
+        let task_id = Uuid::new_v4();
+        let task_type = TaskType::Custom("counter".to_string());
+        sqlx::query(
+            "INSERT INTO tasks (id, name, task_type, cron_expression, one_time, status, created_by)
+            VALUES (?, 'once', ?, '* * * * * *', 1, 'scheduled', ?)",
+        )
+        .bind(task_id)
+        .bind(task_type.to_string())
+        .bind(Uuid::new_v4())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut registry = ExecutorRegistry::new();
+        registry.register(task_type, Counter(runs.clone()));
+        let store = Arc::new(MemoryLeaseStore::default());
+        let cluster = Cluster::new("node".to_string(), store, Duration::from_secs(60));
+        let scheduler = Scheduler::new(pool.clone(), registry, cluster)
+            .await
+            .unwrap();
+        scheduler.lock().await.start().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(3500)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        let task = task::get_task(&pool, task_id).await.unwrap();
+        assert!(!task.is_active);
+        assert_eq!(task.next_run_at, None);
+        assert!(task.last_run_at.is_some());
     }
 }
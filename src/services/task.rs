@@ -1,24 +1,32 @@
 use crate::{
     error::AppError,
-    models::{CreateTaskRequest, ListTasksQuery, ScheduledTask, UpdateTaskRequest},
+    models::{
+        CreateTaskRequest, ListAuditLogsQuery, ListTasksQuery, ScheduledTask, TaskAuditAction,
+        TaskAuditLog, TaskExecution, TaskStatus, UpdateTaskRequest,
+    },
+    services::dependency,
 };
 use chrono::{DateTime, Utc};
-use serde_json::Value;
-use sqlx::sqlite::SqlitePool;
+use serde_json::{json, Map, Value};
+use sqlx::{sqlite::SqlitePool, SqliteExecutor, Transaction};
 use uuid::Uuid;
 
-pub async fn create_task(pool: &SqlitePool, task: CreateTaskRequest) -> Result<(), AppError> {
+pub async fn create_task(
+    executor: impl SqliteExecutor<'_>,
+    task: CreateTaskRequest,
+) -> Result<(), AppError> {
     let task_type = task.task_type.to_string();
 
     sqlx::query!(
         r#"INSERT INTO tasks (
-            id, name, description, task_type, cron_expression, one_time, priority, timeout_seconds, max_retries, retry_delay_seconds, parameters, status, is_active, created_by)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            id, name, description, task_type, cron_expression, timezone, one_time, priority, timeout_seconds, max_retries, retry_delay_seconds, parameters, status, misfire_policy, misfire_max_runs, is_active, created_by)
+        VALUES (?, ?, ?, ?, ?, COALESCE(?, 'UTC'), ?, ?, ?, ?, ?, ?, ?, COALESCE(?, 'skip'), COALESCE(?, 10), ?, ?)"#,
         task.id,
         task.name,
         task.description,
         task_type,
         task.cron_expression,
+        task.timezone,
         task.one_time,
         task.priority,
         task.timeout_seconds,
@@ -26,18 +34,20 @@ pub async fn create_task(pool: &SqlitePool, task: CreateTaskRequest) -> Result<(
         task.retry_delay_seconds,
         task.parameters,
         task.status,
+        task.misfire_policy,
+        task.misfire_max_runs,
         task.is_active,
         task.created_by,
     )
-    .execute(pool)
+    .execute(executor)
     .await
-    .map_err(AppError::Database)?;
+    .map_err(name_conflict)?;
 
     Ok(())
 }
 
 pub async fn update_task(
-    pool: &SqlitePool,
+    executor: impl SqliteExecutor<'_>,
     id: Uuid,
     req: UpdateTaskRequest,
 ) -> Result<(), AppError> {
@@ -47,41 +57,57 @@ pub async fn update_task(
         SET name = COALESCE(?, name),
             description = COALESCE(?, description),
             cron_expression = COALESCE(?, cron_expression),
+            timezone = COALESCE(?, timezone),
             one_time = COALESCE(?, one_time),
             priority = COALESCE(?, priority),
             timeout_seconds = COALESCE(?, timeout_seconds),
             max_retries = COALESCE(?, max_retries),
             retry_delay_seconds = COALESCE(?, retry_delay_seconds),
             parameters = COALESCE(?, parameters),
+            misfire_policy = COALESCE(?, misfire_policy),
+            misfire_max_runs = COALESCE(?, misfire_max_runs),
             is_active = COALESCE(?, is_active),
             status = COALESCE(?, status)
         WHERE id = ?;"#,
         req.name,
         req.description,
         req.cron_expression,
+        req.timezone,
         req.one_time,
         req.priority,
         req.timeout_seconds,
         req.max_retries,
         req.retry_delay_seconds,
         req.parameters,
+        req.misfire_policy,
+        req.misfire_max_runs,
         req.is_active,
         req.status,
         id
     )
-    .fetch_optional(pool)
+    .fetch_optional(executor)
     .await
-    .map_err(AppError::Database)?
+    .map_err(name_conflict)?
     .ok_or_else(|| AppError::NotFound("Task not found".to_string()));
     Ok(())
 }
 
+/// 同一用户的任务名称唯一，重名时返回校验错误而不是数据库错误
+fn name_conflict(error: sqlx::Error) -> AppError {
+    match error.as_database_error() {
+        Some(e) if e.is_unique_violation() => {
+            AppError::Validation("Task name already exists".to_string())
+        }
+        _ => AppError::Database(error),
+    }
+}
+
 pub async fn get_task(pool: &SqlitePool, id: Uuid) -> Result<ScheduledTask, AppError> {
     sqlx::query_as!(
         ScheduledTask,
         r#"SELECT
-            id as "id: Uuid", name, description, task_type as "task_type: String", cron_expression,
-            one_time, priority as "priority: String", timeout_seconds, max_retries, retry_delay_seconds, parameters as "parameters: Value", status as "status: String", is_active, created_by as "created_by: Uuid", next_run_at as "next_run_at: DateTime<Utc>", last_run_at as "last_run_at: DateTime<Utc>", created_at as "created_at: DateTime<Utc>", updated_at as "updated_at: DateTime<Utc>"
+            id as "id: Uuid", name, description, task_type as "task_type: String", cron_expression, timezone,
+            one_time, priority as "priority: String", timeout_seconds, max_retries, retry_delay_seconds, parameters as "parameters: Value", status as "status: String", misfire_policy as "misfire_policy: String", misfire_max_runs, is_active, created_by as "created_by: Uuid", next_run_at as "next_run_at: DateTime<Utc>", last_run_at as "last_run_at: DateTime<Utc>", created_at as "created_at: DateTime<Utc>", updated_at as "updated_at: DateTime<Utc>"
         FROM tasks WHERE id = ?"#,
         id
     )
@@ -101,14 +127,15 @@ pub async fn list_tasks(
     sqlx::query_as!(
         ScheduledTask,
         r#"SELECT
-                id as "id: Uuid", name, description, task_type, cron_expression,
+                id as "id: Uuid", name, description, task_type, cron_expression, timezone,
                 one_time, priority as "priority: String", timeout_seconds, max_retries, retry_delay_seconds,
-                parameters as "parameters: Value", status as "status: String", is_active, created_by as "created_by: Uuid",
+                parameters as "parameters: Value", status as "status: String", misfire_policy as "misfire_policy: String", misfire_max_runs, is_active, created_by as "created_by: Uuid",
                 next_run_at as "next_run_at: DateTime<Utc>", last_run_at as "last_run_at: DateTime<Utc>", created_at as "created_at: DateTime<Utc>", updated_at as "updated_at: DateTime<Utc>"
            FROM tasks
            WHERE (? IS NULL OR status = ?)
            AND (? IS NULL OR priority = ?)
            AND is_active = ?
+           AND (? IS NULL OR created_by = ?)
            ORDER BY created_at DESC
            LIMIT ? OFFSET ?"#,
         query.status,
@@ -116,6 +143,8 @@ pub async fn list_tasks(
         query.priority,
         query.priority,
         query.is_active,
+        query.created_by,
+        query.created_by,
         limit,
         offset
     )
@@ -124,9 +153,32 @@ pub async fn list_tasks(
     .map_err(AppError::Database)
 }
 
+/// 查询全部任务，用于导入导出清单
+pub async fn list_all_tasks(pool: &SqlitePool) -> Result<Vec<ScheduledTask>, AppError> {
+    sqlx::query_as!(
+        ScheduledTask,
+        r#"SELECT
+            id as "id: Uuid", name, description, task_type as "task_type: String", cron_expression, timezone,
+            one_time, priority as "priority: String", timeout_seconds, max_retries, retry_delay_seconds, parameters as "parameters: Value", status as "status: String", misfire_policy as "misfire_policy: String", misfire_max_runs, is_active, created_by as "created_by: Uuid", next_run_at as "next_run_at: DateTime<Utc>", last_run_at as "last_run_at: DateTime<Utc>", created_at as "created_at: DateTime<Utc>", updated_at as "updated_at: DateTime<Utc>"
+        FROM tasks ORDER BY created_at, name"#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::Database)
+}
+
+/// 删除任务及其依赖关系和执行记录，审计日志保留
 pub async fn delete_task(pool: &SqlitePool, id: Uuid) -> Result<(), AppError> {
+    let mut transaction: Transaction<'_, sqlx::Sqlite> = pool.begin().await?;
+    dependency::remove_task_dependencies(&mut *transaction, id).await?;
+
+    sqlx::query!("DELETE FROM task_executions WHERE task_id = ?", id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(AppError::Database)?;
+
     let result = sqlx::query!("DELETE FROM tasks WHERE id = ? ", id)
-        .execute(pool)
+        .execute(&mut *transaction)
         .await
         .map_err(AppError::Database)?;
 
@@ -134,5 +186,468 @@ pub async fn delete_task(pool: &SqlitePool, id: Uuid) -> Result<(), AppError> {
         return Err(AppError::NotFound("Task not found".to_string()));
     }
 
+    transaction.commit().await?;
+    Ok(())
+}
+
+pub async fn update_task_status(
+    pool: &SqlitePool,
+    id: Uuid,
+    status: TaskStatus,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE tasks SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        status,
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(())
+}
+
+/// 停用已触发的一次性任务，不再调度
+pub async fn deactivate_task(pool: &SqlitePool, id: Uuid) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE tasks SET is_active = 0, next_run_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(())
+}
+
+/// 记录任务运行状态，不覆盖已暂停或已取消的任务
+pub async fn update_run_status(
+    pool: &SqlitePool,
+    id: Uuid,
+    status: TaskStatus,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"UPDATE tasks SET status = ?, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ? AND status NOT IN ('paused', 'canceled')"#,
+        status,
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(())
+}
+
+pub async fn set_next_run_at(
+    pool: &SqlitePool,
+    id: Uuid,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE tasks SET next_run_at = ? WHERE id = ?",
+        next_run_at,
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(())
+}
+
+/// 任务触发时记录本次及下次运行时间
+pub async fn record_run_times(
+    pool: &SqlitePool,
+    id: Uuid,
+    last_run_at: DateTime<Utc>,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE tasks SET last_run_at = ?, next_run_at = COALESCE(?, next_run_at) WHERE id = ?",
+        last_run_at,
+        next_run_at,
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(())
+}
+
+pub async fn create_execution(
+    pool: &SqlitePool,
+    execution: &TaskExecution,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"INSERT INTO task_executions (
+            id, task_id, status, started_at, node_id, attempt_number, parameters, is_catch_up, scheduled_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        execution.id,
+        execution.task_id,
+        execution.status,
+        execution.started_at,
+        execution.node_id,
+        execution.attempt_number,
+        execution.parameters,
+        execution.is_catch_up,
+        execution.scheduled_at,
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(())
+}
+
+pub async fn finish_execution(
+    pool: &SqlitePool,
+    id: Uuid,
+    status: TaskStatus,
+    duration_ms: i64,
+    error_message: Option<String>,
+    result: Option<Value>,
+) -> Result<(), AppError> {
+    let completed_at = Utc::now();
+    sqlx::query!(
+        r#"UPDATE task_executions
+        SET status = ?,
+            completed_at = ?,
+            duration_ms = ?,
+            error_message = ?,
+            result = ?,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?"#,
+        status,
+        completed_at,
+        duration_ms,
+        error_message,
+        result,
+        id
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(())
+}
+
+/// 删除早于 `before` 的任务日志，每次最多删除 `limit` 条
+pub async fn delete_task_logs_before(
+    pool: &SqlitePool,
+    before: DateTime<Utc>,
+    limit: i64,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM task_logs WHERE id IN (
+            SELECT id FROM task_logs WHERE datetime(created_at) < datetime(?) LIMIT ?
+        )",
+        before,
+        limit
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn count_task_logs_before(
+    pool: &SqlitePool,
+    before: DateTime<Utc>,
+) -> Result<i64, AppError> {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM task_logs WHERE datetime(created_at) < datetime(?)"#,
+        before
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(AppError::Database)
+}
+
+/// 审计 diff 忽略的字段，这些字段由调度器自动维护
+const AUDIT_IGNORED_FIELDS: [&str; 3] = ["updated_at", "next_run_at", "last_run_at"];
+
+/// 审计 diff 中只记录是否变更的字段，任务参数可能包含凭据
+const AUDIT_REDACTED_FIELDS: [&str; 1] = ["parameters"];
+
+/// 记录任务审计日志
+pub async fn record_audit(
+    pool: &SqlitePool,
+    task_id: Uuid,
+    user_id: Uuid,
+    action: TaskAuditAction,
+    details: Value,
+) -> Result<(), AppError> {
+    let id = Uuid::new_v4();
+    let action = action.to_string();
+    sqlx::query!(
+        "INSERT INTO task_logs (id, task_id, user_id, action, details) VALUES (?, ?, ?, ?, ?)",
+        id,
+        task_id,
+        user_id,
+        action,
+        details
+    )
+    .execute(pool)
+    .await
+    .map_err(AppError::Database)?;
+
     Ok(())
 }
+
+/// 查询任务的审计日志，按时间倒序
+pub async fn list_audit_logs(
+    pool: &SqlitePool,
+    task_id: Uuid,
+    query: ListAuditLogsQuery,
+) -> Result<Vec<TaskAuditLog>, AppError> {
+    let limit = query.size.unwrap_or(20).clamp(1, 100);
+    let offset = (query.page.unwrap_or(1).max(1) - 1) * limit;
+
+    sqlx::query_as!(
+        TaskAuditLog,
+        r#"SELECT
+            id as "id: Uuid", task_id as "task_id: Uuid", action, user_id as "user_id: Uuid", details as "details!: Value", created_at as "created_at: DateTime<Utc>"
+        FROM task_logs
+        WHERE task_id = ? AND (? IS NULL OR action = ?)
+        ORDER BY created_at DESC, rowid DESC
+        LIMIT ? OFFSET ?"#,
+        task_id,
+        query.action,
+        query.action,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::Database)
+}
+
+/// 比较任务变更前后的字段，返回 `{"字段": {"from": 旧值, "to": 新值}}`
+///
+/// 创建时 `before` 为空，删除时 `after` 为空；`parameters` 只记录 `{"changed": true}`。
+pub fn audit_diff(before: Option<&ScheduledTask>, after: Option<&ScheduledTask>) -> Value {
+    let fields = |task: Option<&ScheduledTask>| match task.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => Map::new(),
+    };
+    let (before, after) = (fields(before), fields(after));
+
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        if AUDIT_IGNORED_FIELDS.contains(&key.as_str()) || changes.contains_key(key) {
+            continue;
+        }
+        let (from, to) = (
+            before.get(key).unwrap_or(&Value::Null),
+            after.get(key).unwrap_or(&Value::Null),
+        );
+        if from == to {
+            continue;
+        }
+        if AUDIT_REDACTED_FIELDS.contains(&key.as_str()) {
+            changes.insert(key.clone(), json!({ "changed": true }));
+        } else {
+            changes.insert(key.clone(), json!({ "from": from, "to": to }));
+        }
+    }
+    Value::Object(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MisfirePolicy, TaskPriority, TaskStatus, TaskType};
+
+    fn task(parameters: Value) -> ScheduledTask {
+        ScheduledTask {
+            id: Uuid::nil(),
+            name: "backup".to_string(),
+            description: None,
+            task_type: TaskType::DataBackup,
+            cron_expression: Some("0 3 * * *".to_string()),
+            timezone: "UTC".to_string(),
+            one_time: false,
+            priority: TaskPriority::Medium,
+            timeout_seconds: None,
+            max_retries: 0,
+            retry_delay_seconds: 60,
+            parameters: Some(parameters),
+            status: TaskStatus::Scheduled,
+            misfire_policy: MisfirePolicy::Skip,
+            misfire_max_runs: 1,
+            is_active: true,
+            created_by: Uuid::nil(),
+            created_at: None,
+            updated_at: None,
+            next_run_at: None,
+            last_run_at: None,
+        }
+    }
+
+    #[test]
+    fn audit_diff_hides_parameter_values() {
+        let before = task(json!({ "password": "old-secret" }));
+        let mut after = task(json!({ "password": "new-secret" }));
+        after.name = "nightly backup".to_string();
+
+        let diff = audit_diff(Some(&before), Some(&after));
+        assert_eq!(
+            diff,
+            json!({
+                "name": { "from": "backup", "to": "nightly backup" },
+                "parameters": { "changed": true },
+            })
+        );
+        assert!(!diff.to_string().contains("secret"));
+
+        let created = audit_diff(None, Some(&after));
+        assert_eq!(created["parameters"], json!({ "changed": true }));
+        assert!(!created.to_string().contains("secret"));
+
+        let unchanged = audit_diff(Some(&after), Some(&after.clone()));
+        assert_eq!(unchanged, json!({}));
+    }
+
+    async fn migrated_pool() -> SqlitePool {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn insert_user(pool: &SqlitePool, username: &str) -> Uuid {
+        let user_id = Uuid::new_v4();
+        let email = format!("{}@example.com", username);
+        sqlx::query!(
+            "INSERT INTO users (id, username, email, password, salt) VALUES (?, ?, ?, '', '')",
+            user_id,
+            username,
+            email
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        user_id
+    }
+
+    fn create_request(id: Uuid, created_by: Uuid) -> CreateTaskRequest {
+        let task = task(json!({}));
+        CreateTaskRequest {
+            id,
+            name: task.name,
+            description: None,
+            task_type: task.task_type,
+            cron_expression: task.cron_expression,
+            timezone: None,
+            one_time: Some(task.one_time),
+            priority: Some(task.priority),
+            timeout_seconds: None,
+            max_retries: Some(0),
+            retry_delay_seconds: Some(60),
+            parameters: None,
+            misfire_policy: None,
+            misfire_max_runs: None,
+            is_active: Some(true),
+            status: TaskStatus::Scheduled,
+            created_by,
+        }
+    }
+
+    #[tokio::test]
+    async fn delete_task_removes_its_executions() {
+        let pool = migrated_pool().await;
+        let user_id = insert_user(&pool, "owner").await;
+
+        let task_id = Uuid::new_v4();
+        create_task(&pool, create_request(task_id, user_id))
+            .await
+            .unwrap();
+        create_execution(
+            &pool,
+            &TaskExecution {
+                id: Uuid::new_v4(),
+                task_id,
+                status: TaskStatus::Completed,
+                started_at: Some(Utc::now()),
+                completed_at: None,
+                duration_ms: None,
+                error_message: None,
+                node_id: None,
+                attempt_number: 1,
+                parameters: None,
+                result: None,
+                is_catch_up: false,
+                scheduled_at: None,
+                created_at: None,
+                updated_at: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        delete_task(&pool, task_id).await.unwrap();
+
+        let executions = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!: i64" FROM task_executions WHERE task_id = ?"#,
+            task_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(executions, 0);
+        assert!(matches!(
+            get_task(&pool, task_id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn task_names_are_unique_per_user() {
+        let pool = migrated_pool().await;
+        let owner = insert_user(&pool, "owner").await;
+        let other = insert_user(&pool, "other").await;
+
+        let first = Uuid::new_v4();
+        create_task(&pool, create_request(first, owner))
+            .await
+            .unwrap();
+        assert!(matches!(
+            create_task(&pool, create_request(Uuid::new_v4(), owner)).await,
+            Err(AppError::Validation(_))
+        ));
+        create_task(&pool, create_request(Uuid::new_v4(), other))
+            .await
+            .unwrap();
+
+        let mut renamed = create_request(Uuid::new_v4(), owner);
+        renamed.name = "restore".to_string();
+        let second = renamed.id;
+        create_task(&pool, renamed).await.unwrap();
+        let rename = UpdateTaskRequest {
+            name: Some("backup".to_string()),
+            description: None,
+            cron_expression: None,
+            timezone: None,
+            one_time: None,
+            priority: None,
+            timeout_seconds: None,
+            max_retries: None,
+            retry_delay_seconds: None,
+            parameters: None,
+            misfire_policy: None,
+            misfire_max_runs: None,
+            is_active: None,
+            status: None,
+        };
+        assert!(matches!(
+            update_task(&pool, second, rename).await,
+            Err(AppError::Validation(_))
+        ));
+    }
+}
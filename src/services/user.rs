@@ -5,7 +5,7 @@ use sqlx::Transaction;
 use uuid::Uuid;
 
 use crate::models::ListUsersQuery;
-use crate::{error::AppError, models::user::{User, UpdateUserRequest}};
+use crate::{error::AppError, models::user::{User, UpdateUserRequest, UserRole}};
 
 pub async fn get_user_by_id(pool: &SqlitePool, id: Uuid) -> Result<User, AppError> {
     let user = sqlx::query_as!(
@@ -242,3 +242,55 @@ pub async fn update_user_avatar(pool: &SqlitePool, id: Uuid, avatar: &str) -> Re
     transaction.commit().await?;
     Ok(user)
 }
+
+/// 删除已过期的验证码，每次最多删除 `limit` 条
+pub async fn delete_expired_verification_codes(
+    pool: &SqlitePool,
+    limit: i64,
+) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM verification_codes WHERE id IN (
+            SELECT id FROM verification_codes WHERE expires_at < CURRENT_TIMESTAMP LIMIT ?
+        )",
+        limit
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn count_expired_verification_codes(pool: &SqlitePool) -> Result<i64, AppError> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!: i64" FROM verification_codes WHERE expires_at < CURRENT_TIMESTAMP"#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+/// 所有用户头像的文件路径
+pub async fn list_avatar_paths(pool: &SqlitePool) -> Result<Vec<String>, AppError> {
+    let avatars = sqlx::query_scalar!("SELECT avatar FROM users WHERE avatar IS NOT NULL")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(avatars.into_iter().flatten().collect())
+}
+
+/// 指定角色下所有启用用户的邮箱
+pub async fn list_emails_by_role(
+    pool: &SqlitePool,
+    role: &UserRole,
+) -> Result<Vec<String>, AppError> {
+    let role = role.to_string();
+    let emails = sqlx::query_scalar!(
+        "SELECT email FROM users WHERE role = ? AND is_active = 1 ORDER BY email",
+        role
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(emails)
+}
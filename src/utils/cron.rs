@@ -1,6 +1,452 @@
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use cron::Schedule;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
+use crate::error::AppError;
+
+/// 6/7 段表达式各字段名称
+const FIELD_NAMES: [&str; 7] = [
+    "seconds",
+    "minutes",
+    "hours",
+    "day_of_month",
+    "month",
+    "day_of_week",
+    "year",
+];
+
+/// cron 表达式解析错误及其位置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CronError {
+    pub message: String,
+    pub field: Option<String>,   // 出错的字段
+    pub position: Option<usize>, // 出错字段在原表达式中的字符位置
+}
+
 pub fn validate_cron(expression: &str) -> bool {
-    Schedule::from_str(expression).is_ok()
+    parse_cron(expression).is_ok()
+}
+
+/// 解析 cron 表达式，支持 5 段 crontab 写法
+pub fn parse_cron(expression: &str) -> Result<Schedule, CronError> {
+    let normalized = normalize_cron(expression);
+    Schedule::from_str(&normalized).map_err(|e| locate_error(expression, &normalized, e))
+}
+
+/// 将 5 段 crontab 表达式转换为 `cron` crate 使用的 6 段格式
+///
+/// 补齐秒字段，并将星期从 crontab 的 0-7（0 和 7 均为周日）转换为 1-7（1 为周日）。
+pub fn normalize_cron(expression: &str) -> String {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    if fields.len() != 5 {
+        return fields.join(" ");
+    }
+
+    format!(
+        "0 {} {} {} {} {}",
+        fields[0],
+        fields[1],
+        fields[2],
+        fields[3],
+        crontab_day_of_week(fields[4])
+    )
+}
+
+/// 解析 IANA 时区名称，例如 `Asia/Shanghai`
+pub fn parse_timezone(name: &str) -> Result<Tz, AppError> {
+    name.parse()
+        .map_err(|_| AppError::Validation(format!("Invalid timezone: {}", name)))
+}
+
+/// 计算任务在 `after` 之后的下一次运行时间
+pub fn next_run(expression: &str, timezone: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let schedule = parse_cron(expression).ok()?;
+    let tz = parse_timezone(timezone).ok()?;
+    next_fire(&schedule, tz, after)
+}
+
+/// 按时区的本地时间匹配 cron 表达式，计算 `after` 之后的下一次触发时间
+///
+/// 夏令时开始时被跳过的本地时间在跳变后立即触发；
+/// 夏令时结束时重复出现的本地时间只在第一次出现时触发。
+pub fn next_fire(schedule: &Schedule, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    // 以 UTC 表示本地时间，避免 cron crate 直接丢弃不存在或重复的本地时间
+    let local_after = Utc.from_utc_datetime(&after.with_timezone(&tz).naive_local());
+    schedule
+        .after(&local_after)
+        .filter_map(|candidate| resolve_local(tz, candidate.naive_utc()))
+        .find(|time| *time > after)
+}
+
+/// 计算 `(since, until]` 区间内最近的 `keep` 次错过的触发时间，按时间先后返回
+///
+/// 从 `until` 向前查找，最多检查 `keep + 1` 次触发，停机时间再长也不会逐个遍历；
+/// 第二个返回值表示是否还有更早的触发被省略。
+pub fn missed_fires(
+    schedule: &Schedule,
+    tz: Tz,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    keep: usize,
+) -> (Vec<DateTime<Utc>>, bool) {
+    // 与 next_fire 相同以 UTC 表示本地时间，向后多取一秒使 `until` 本身也包含在内
+    let local_until =
+        Utc.from_utc_datetime(&until.with_timezone(&tz).naive_local()) + Duration::seconds(1);
+    let candidates = schedule
+        .after(&local_until)
+        .rev()
+        .filter_map(|candidate| resolve_local(tz, candidate.naive_utc()))
+        .skip_while(|time| *time > until)
+        .take_while(|time| *time > since);
+
+    let mut fires: Vec<DateTime<Utc>> = Vec::new();
+    let mut omitted = false;
+    for time in candidates {
+        // 夏令时跳变区间内的多个本地时间会落到同一时刻
+        if fires.last() == Some(&time) {
+            continue;
+        }
+        if fires.len() == keep {
+            omitted = true;
+            break;
+        }
+        fires.push(time);
+    }
+    fires.reverse();
+    (fires, omitted)
+}
+
+/// 计算指定时区下接下来的若干次触发时间
+pub fn upcoming(schedule: &Schedule, tz: Tz, count: usize) -> Vec<DateTime<Tz>> {
+    let mut times = Vec::with_capacity(count);
+    let mut after = Utc::now();
+    while times.len() < count {
+        match next_fire(schedule, tz, after) {
+            Some(next) => {
+                times.push(next.with_timezone(&tz));
+                after = next;
+            }
+            None => break,
+        }
+    }
+    times
+}
+
+fn resolve_local(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(time) => Some(time.with_timezone(&Utc)),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest.with_timezone(&Utc)),
+        // 落在夏令时跳变区间内，取跳变后的第一个有效时间
+        LocalResult::None => (1..=180).find_map(|minutes| {
+            tz.from_local_datetime(&(local + Duration::minutes(minutes)))
+                .earliest()
+                .map(|time| time.with_timezone(&Utc))
+        }),
+    }
+}
+
+fn crontab_day_of_week(field: &str) -> String {
+    field
+        .split(',')
+        .map(|item| {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (item, None),
+            };
+            let step_suffix = step.map(|step| format!("/{}", step)).unwrap_or_default();
+            match range.split_once('-') {
+                // 以 7 结尾的区间需要拆成 "a-7,1"，有步长时仅在步长落到 7 上时追加 1
+                Some((start, "7")) => match start.parse::<u8>() {
+                    Ok(0) => format!("1-7{}", step_suffix),
+                    Ok(start) if start < 7 => {
+                        let sunday = step.is_none_or(|step| {
+                            step.parse::<u8>()
+                                .is_ok_and(|step| step > 0 && (7 - start) % step == 0)
+                        });
+                        let days = format!("{}-7{}", start + 1, step_suffix);
+                        if sunday {
+                            format!("{},1", days)
+                        } else {
+                            days
+                        }
+                    }
+                    _ => format!("{}-{}{}", shift_day(start), shift_day("7"), step_suffix),
+                },
+                Some((start, end)) => {
+                    format!("{}-{}{}", shift_day(start), shift_day(end), step_suffix)
+                }
+                None => format!("{}{}", shift_day(range), step_suffix),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn shift_day(day: &str) -> String {
+    match day.parse::<u8>() {
+        Ok(n) if n <= 7 => (n % 7 + 1).to_string(),
+        _ => day.to_string(),
+    }
+}
+
+/// 逐个字段校验，定位解析失败的字段
+fn locate_error(original: &str, normalized: &str, error: cron::error::Error) -> CronError {
+    let fields: Vec<&str> = normalized.split_whitespace().collect();
+    if !(6..=7).contains(&fields.len()) {
+        let count = original.split_whitespace().count();
+        return CronError {
+            message: format!("Expected 5, 6 or 7 fields, found {}", count),
+            field: None,
+            position: None,
+        };
+    }
+
+    // 5 段表达式补了秒字段，字段下标需要回退一位
+    let offset = usize::from(original.split_whitespace().count() == 5);
+    for (index, field) in fields.iter().enumerate() {
+        let mut probe = vec!["*"; fields.len()];
+        probe[0] = "0";
+        probe[index] = field;
+        if Schedule::from_str(&probe.join(" ")).is_err() {
+            let position = index
+                .checked_sub(offset)
+                .and_then(|i| field_position(original, i));
+            return CronError {
+                message: format!("Invalid {} field: {}", FIELD_NAMES[index], field),
+                field: Some(FIELD_NAMES[index].to_string()),
+                position,
+            };
+        }
+    }
+
+    CronError {
+        message: error.to_string(),
+        field: None,
+        position: None,
+    }
+}
+
+fn field_position(expression: &str, index: usize) -> Option<usize> {
+    let mut in_field = false;
+    let mut current = 0;
+    for (pos, c) in expression.char_indices() {
+        if c.is_whitespace() {
+            if in_field {
+                current += 1;
+            }
+            in_field = false;
+        } else if !in_field {
+            if current == index {
+                return Some(pos);
+            }
+            in_field = true;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn normalize_cron_converts_crontab() {
+        assert_eq!(normalize_cron("*/5 * * * *"), "0 */5 * * * *");
+        assert_eq!(normalize_cron("  0   3 * *  1-5 "), "0 0 3 * * 2-6");
+        // 0 和 7 都表示周日
+        assert_eq!(normalize_cron("0 0 * * 0"), "0 0 0 * * 1");
+        assert_eq!(normalize_cron("0 0 * * 7"), "0 0 0 * * 1");
+        assert_eq!(normalize_cron("0 0 * * 0-7"), "0 0 0 * * 1-7");
+        assert_eq!(normalize_cron("0 0 * * 5-7"), "0 0 0 * * 6-7,1");
+        assert_eq!(normalize_cron("0 0 * * 1-7/2"), "0 0 0 * * 2-7/2,1");
+        assert_eq!(normalize_cron("0 0 * * 2-7/2"), "0 0 0 * * 3-7/2");
+        assert_eq!(normalize_cron("0 0 * * 0-7/3"), "0 0 0 * * 1-7/3");
+        assert!(validate_cron("0 0 * * 1-7/2"));
+        assert_eq!(normalize_cron("0 0 * * 0,3/2"), "0 0 0 * * 1,4/2");
+        assert_eq!(normalize_cron("0 0 * * MON-FRI"), "0 0 0 * * MON-FRI");
+        // 6/7 段表达式保持原样
+        assert_eq!(normalize_cron("30 0 3 * * 1"), "30 0 3 * * 1");
+        assert_eq!(normalize_cron("0 0 3 * * * 2030"), "0 0 3 * * * 2030");
+    }
+
+    #[test]
+    fn crontab_and_cron_weekdays_agree() {
+        // 2026-01-05 为周一
+        let after = utc("2026-01-04T00:00:00Z");
+        let crontab = parse_cron("0 9 * * 1").unwrap();
+        let quartz = parse_cron("0 0 9 * * MON").unwrap();
+        assert_eq!(
+            next_fire(&crontab, Tz::UTC, after),
+            Some(utc("2026-01-05T09:00:00Z"))
+        );
+        assert_eq!(
+            next_fire(&crontab, Tz::UTC, after),
+            next_fire(&quartz, Tz::UTC, after)
+        );
+    }
+
+    #[test]
+    fn locate_error_points_at_field() {
+        let error = parse_cron("0 25 * * *").unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("hours"));
+        assert_eq!(error.position, Some(2));
+
+        let error = parse_cron("0 0 3 32 * *").unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("day_of_month"));
+        assert_eq!(error.position, Some(6));
+
+        let error = parse_cron("*  * * 13 *").unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("month"));
+        assert_eq!(error.position, Some(7));
+
+        let error = parse_cron("* * *").unwrap_err();
+        assert_eq!(error.field, None);
+        assert_eq!(error.message, "Expected 5, 6 or 7 fields, found 3");
+
+        assert!(validate_cron("*/15 9-17 * * 1-5"));
+        assert!(!validate_cron(""));
+    }
+
+    #[test]
+    fn resolve_local_handles_gaps_and_overlaps() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let local = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(
+            resolve_local(tz, local("2026-06-01 09:00:00")),
+            Some(utc("2026-06-01T13:00:00Z"))
+        );
+        // 02:30 在春季跳变中不存在，顺延到 03:00 EDT
+        assert_eq!(
+            resolve_local(tz, local("2026-03-08 02:30:00")),
+            Some(utc("2026-03-08T07:00:00Z"))
+        );
+        // 01:30 在秋季回拨时出现两次，取较早的 EDT
+        assert_eq!(
+            resolve_local(tz, local("2026-11-01 01:30:00")),
+            Some(utc("2026-11-01T05:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_fire_across_dst() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let schedule = parse_cron("30 2 * * *").unwrap();
+        // 春季跳变当天在跳变后立即触发，次日恢复 02:30 EDT
+        let fire = next_fire(&schedule, tz, utc("2026-03-08T00:00:00Z")).unwrap();
+        assert_eq!(fire, utc("2026-03-08T07:00:00Z"));
+        assert_eq!(
+            next_fire(&schedule, tz, fire),
+            Some(utc("2026-03-09T06:30:00Z"))
+        );
+
+        let schedule = parse_cron("30 1 * * *").unwrap();
+        // 秋季回拨当天重复的 01:30 只触发一次
+        let fire = next_fire(&schedule, tz, utc("2026-11-01T00:00:00Z")).unwrap();
+        assert_eq!(fire, utc("2026-11-01T05:30:00Z"));
+        assert_eq!(
+            next_fire(&schedule, tz, fire),
+            Some(utc("2026-11-02T06:30:00Z"))
+        );
+
+        // 每 15 分钟的任务在跳变区间内不会重复触发
+        let schedule = parse_cron("*/15 * * * *").unwrap();
+        let fire = next_fire(&schedule, tz, utc("2026-03-08T06:45:00Z")).unwrap();
+        assert_eq!(fire, utc("2026-03-08T07:00:00Z"));
+        assert_eq!(
+            next_fire(&schedule, tz, fire),
+            Some(utc("2026-03-08T07:15:00Z"))
+        );
+    }
+
+    #[test]
+    fn missed_fires_keeps_latest_runs() {
+        let schedule = parse_cron("0 * * * *").unwrap();
+        let (fires, omitted) = missed_fires(
+            &schedule,
+            Tz::UTC,
+            utc("2026-01-01T00:00:00Z"),
+            utc("2026-01-01T05:00:00Z"),
+            3,
+        );
+        assert_eq!(
+            fires,
+            vec![
+                utc("2026-01-01T03:00:00Z"),
+                utc("2026-01-01T04:00:00Z"),
+                utc("2026-01-01T05:00:00Z"),
+            ]
+        );
+        assert!(omitted);
+
+        let (fires, omitted) = missed_fires(
+            &schedule,
+            Tz::UTC,
+            utc("2026-01-01T00:00:00Z"),
+            utc("2026-01-01T02:30:00Z"),
+            3,
+        );
+        assert_eq!(
+            fires,
+            vec![utc("2026-01-01T01:00:00Z"), utc("2026-01-01T02:00:00Z")]
+        );
+        assert!(!omitted);
+    }
+
+    #[test]
+    fn missed_fires_does_not_walk_long_outages() {
+        // 每秒触发、停机十年，只检查最近几次
+        let schedule = parse_cron("* * * * * *").unwrap();
+        let (fires, omitted) = missed_fires(
+            &schedule,
+            Tz::UTC,
+            utc("2016-01-01T00:00:00Z"),
+            utc("2026-01-01T00:00:00Z"),
+            2,
+        );
+        assert_eq!(
+            fires,
+            vec![utc("2025-12-31T23:59:59Z"), utc("2026-01-01T00:00:00Z")]
+        );
+        assert!(omitted);
+    }
+
+    #[test]
+    fn missed_fires_across_dst() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        // 2026-03-08 02:30 不存在，跳变后在 03:00（07:00Z）触发
+        let schedule = parse_cron("30 2 * * *").unwrap();
+        let (fires, omitted) = missed_fires(
+            &schedule,
+            tz,
+            utc("2026-03-07T00:00:00Z"),
+            utc("2026-03-09T12:00:00Z"),
+            10,
+        );
+        assert_eq!(
+            fires,
+            vec![
+                utc("2026-03-07T07:30:00Z"),
+                utc("2026-03-08T07:00:00Z"),
+                utc("2026-03-09T06:30:00Z"),
+            ]
+        );
+        assert!(!omitted);
+
+        // 2026-11-01 01:30 出现两次，只在第一次触发
+        let schedule = parse_cron("30 1 * * *").unwrap();
+        let (fires, _) = missed_fires(
+            &schedule,
+            tz,
+            utc("2026-10-31T12:00:00Z"),
+            utc("2026-11-01T12:00:00Z"),
+            10,
+        );
+        assert_eq!(fires, vec![utc("2026-11-01T05:30:00Z")]);
+    }
 }
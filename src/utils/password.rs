@@ -19,7 +19,7 @@ pub fn generate(password: &str, salt: Option<&str>) -> Result<(String, String),
 
 pub fn verify(password: &str, password_hash: &str) -> Result<bool, AppError> {
     let argon2 = Argon2::default();
-    let parsed_password = PasswordHash::new(&password_hash).unwrap();
+    let parsed_password = PasswordHash::new(password_hash).unwrap();
     Ok(argon2
         .verify_password(password.as_bytes(), &parsed_password)
         .is_ok())
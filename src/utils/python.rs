@@ -1,38 +1,1250 @@
 use chrono::Utc;
+use pyo3::{exceptions::PySyntaxError, PyErr, PyResult, Python};
+use serde::Serialize;
+use serde_json::Value;
+#[cfg(target_os = "linux")]
+use std::{
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    sync::atomic::{AtomicI32, Ordering},
+};
+use std::{
+    io,
+    os::unix::{
+        fs::chown,
+        process::{CommandExt, ExitStatusExt},
+    },
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::{ChildStderr, ChildStdin, ChildStdout},
+    sync::mpsc,
+    time::sleep,
+};
+use tracing::warn;
+use uuid::Uuid;
 
 use crate::{
     error::AppError,
-    models::program::{ProgramCompileResponse, ProgramStatus},
+    models::program::{ProgramCompileResponse, ProgramRunCommand, ProgramStatus},
 };
 
-#[derive(Clone)]
-pub struct PythonExecutor;
+const PYTHON_BIN: &str = "python3";
+
+/// 语法检查时使用的文件名，出现在错误信息中
+const SCRIPT_NAME: &str = "<program>";
+
+/// 沙箱进程可见的 PATH，其余环境变量不继承
+const SANDBOX_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// 沙箱内的工作目录，同时作为 HOME 和 TMPDIR
+const SANDBOX_WORKDIR: &str = "/work";
+
+/// 以只读方式挂载到沙箱根目录的系统路径，符号链接按原样重建，不存在的路径跳过
+#[cfg(target_os = "linux")]
+const SANDBOX_SYSTEM_PATHS: [&str; 7] =
+    ["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc"];
+
+/// 挂载到沙箱 /dev 下的设备
+#[cfg(target_os = "linux")]
+const SANDBOX_DEVICES: [&str; 4] = ["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+
+/// 沙箱使用的命名空间，`deny_network` 时另加网络命名空间
+#[cfg(target_os = "linux")]
+const SANDBOX_NAMESPACES: libc::c_int = libc::CLONE_NEWNS | libc::CLONE_NEWIPC | libc::CLONE_NEWPID;
+
+/// 程序进程的资源限制和运行身份
+#[derive(Debug, Clone, Copy)]
+pub struct SandboxLimits {
+    pub cpu_seconds: u64,    // CPU 时间
+    pub wall_seconds: u64,   // 实际运行时间
+    pub memory_bytes: u64,   // 地址空间上限
+    pub output_bytes: usize, // 标准输出与标准错误合计上限
+    pub input_bytes: usize,  // 写入标准输入的合计上限
+    pub max_processes: u64,  // 运行用户的进程（含线程）数上限，所有运行共享
+    pub deny_network: bool,  // 在独立的网络命名空间中运行
+    pub uid: u32,            // 运行程序的用户，应为专用的低权限用户
+    pub gid: u32,            // 运行程序的用户组
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            cpu_seconds: 10,
+            wall_seconds: 30,
+            memory_bytes: 256 * 1024 * 1024,
+            output_bytes: 1024 * 1024,
+            input_bytes: 1024 * 1024,
+            max_processes: 64,
+            deny_network: true,
+            uid: 65534,
+            gid: 65534,
+        }
+    }
+}
+
+/// 运行中触发的资源限制
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitViolation {
+    CpuTime,
+    WallTime,
+    Memory,
+    Output,
+    Input,
+}
+
+impl LimitViolation {
+    pub fn describe(&self, limits: &SandboxLimits) -> String {
+        match self {
+            Self::CpuTime => format!("CPU time limit exceeded ({}s)", limits.cpu_seconds),
+            Self::WallTime => format!("Wall clock limit exceeded ({}s)", limits.wall_seconds),
+            Self::Memory => format!(
+                "Memory limit exceeded ({} MB)",
+                limits.memory_bytes / 1024 / 1024
+            ),
+            Self::Output => format!("Output limit exceeded ({} bytes)", limits.output_bytes),
+            Self::Input => format!("Input limit exceeded ({} bytes)", limits.input_bytes),
+        }
+    }
+}
+
+/// 程序进程的资源使用情况
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ResourceUsage {
+    pub cpu_user_ms: i64,   // 用户态 CPU 时间
+    pub cpu_system_ms: i64, // 内核态 CPU 时间
+    pub max_rss_kb: i64,    // 最大常驻内存
+}
+
+/// 一次程序运行的输出
+#[derive(Debug, Serialize)]
+pub struct PythonOutput {
+    pub exit_code: Option<i32>,            // 退出码，被信号终止时为空
+    pub stdout: String,                    // 标准输出
+    pub stderr: String,                    // 标准错误
+    pub duration_ms: i64,                  // 运行耗时
+    pub violation: Option<LimitViolation>, // 触发的资源限制
+    pub network_isolated: bool,            // 是否已隔离网络
+    pub killed: bool,                      // 是否被客户端结束
+    pub usage: ResourceUsage,              // 资源使用情况
+}
+
+impl PythonOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0) && self.violation.is_none()
+    }
+}
+
+/// 在受限的子进程中运行 Python 程序
+///
+/// 程序不继承服务的环境变量，通过 rlimit 限制 CPU 时间、内存和进程数。
+///
+/// 在 Linux 上且服务以 root（CAP_SYS_ADMIN、CAP_SETUID、CAP_SETGID）运行时，程序以专用的
+/// 低权限用户运行；在独立的挂载命名空间中 chroot 到只包含只读系统目录和本次运行工作目录的
+/// 根目录，看不到服务的文件和 /proc；程序是独立 PID 命名空间的 init 进程，它退出或被结束时
+/// 命名空间内的所有进程随之结束，即使其子进程通过 `setsid()` 离开了进程组；
+/// 按配置在独立的网络命名空间中禁止访问网络。
+///
+/// 无法创建命名空间（其他平台或权限不足）时记录警告，只使用资源限制：程序在工作目录中
+/// 直接运行，服务为 root 时仍切换到运行用户，结束时按进程组结束其子进程。
+#[derive(Clone, Default)]
+pub struct PythonExecutor {
+    limits: SandboxLimits,
+}
 
 impl PythonExecutor {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    pub fn with_limits(mut self, limits: SandboxLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &SandboxLimits {
+        &self.limits
+    }
+
+    /// 使用 CPython 的 `compile()` 检查语法，成功时返回经 `ast.unparse` 规范化的源码
+    ///
+    /// `ast.unparse` 需要 Python 3.9 及以上，更早的解释器直接返回原始源码。
+    ///
+    /// 失败时返回的响应中包含 `SyntaxError` 的类型、行号、文件和信息。
+    pub async fn compile(&self, code: &str) -> Result<String, ProgramCompileResponse> {
+        let code = code.to_string();
+        tokio::task::spawn_blocking(move || check_syntax(&code))
+            .await
+            .unwrap_or_else(|e| {
+                Err(compile_error(
+                    Some("InternalError".to_string()),
+                    None,
+                    Some(e.to_string()),
+                    None,
+                ))
+            })
+    }
+
+    /// 运行代码，`input` 以 JSON 写入标准输入
+    ///
+    /// 脚本可通过 `json.load(sys.stdin)` 读取输入；future 被丢弃（超时、取消）时进程随之结束。
+    pub async fn run(&self, code: &str, input: &Value) -> Result<PythonOutput, AppError> {
+        let (sender, commands) = mpsc::channel(1);
+        let _ = sender.try_send(ProgramRunCommand::Input {
+            data: input.to_string(),
+        });
+        drop(sender);
+        self.run_sandboxed(code, commands, |_, _| {}).await
     }
 
-    pub async fn compile(
+    /// 交互式运行代码
+    ///
+    /// `commands` 中的输入写入标准输入，`Eof` 或通道关闭时关闭标准输入，`Kill` 结束程序；
+    /// 标准输出和标准错误每产生一行（不含换行符）、每写入一次输入回调一次；
+    /// 输入合计超过 `input_bytes` 时结束程序。
+    pub async fn execute_with_updates(
+        &self,
+        code: &str,
+        commands: mpsc::Receiver<ProgramRunCommand>,
+        updates: impl FnMut(StdStream, String),
+    ) -> Result<PythonOutput, AppError> {
+        self.run_sandboxed(code, commands, updates).await
+    }
+
+    async fn run_sandboxed(
         &self,
-        _code: &str,
-    ) -> Result<ProgramCompileResponse, ProgramCompileResponse> {
-        Ok(ProgramCompileResponse {
-            status: ProgramStatus::Compiled,
-            time: Utc::now(),
-            error_file: None,
-            error_type: None,
-            error_line: None,
-            error_message: None,
-            error_suggestions: None,
+        code: &str,
+        mut commands: mpsc::Receiver<ProgramRunCommand>,
+        mut on_line: impl FnMut(StdStream, String),
+    ) -> Result<PythonOutput, AppError> {
+        if self.limits.uid == 0 || self.limits.gid == 0 {
+            return Err(AppError::Server(
+                "Programs must not run as root".to_string(),
+            ));
+        }
+        let isolated = namespaces_available();
+        // 只有 root 可以切换到运行用户，否则以服务自身的用户运行
+        let switch_user = unsafe { libc::geteuid() } == 0;
+        let scratch = ScratchDir::create().await?;
+        let mounts = scratch
+            .prepare(code, &self.limits, isolated, switch_user)
+            .await
+            .map_err(|e| AppError::Server(format!("Failed to prepare program sandbox: {}", e)))?;
+
+        let started = Instant::now();
+        let mut child = self
+            .command(&scratch, mounts, switch_user)
+            .spawn()
+            .map_err(|e| AppError::Server(format!("Failed to start program sandbox: {}", e)))?;
+        let pid = child.id() as i32;
+        let mut sandbox = SandboxProcess::new(pid, isolated);
+        // 自行回收进程以取得资源使用情况；future 被丢弃时沙箱随之结束，等待随即返回
+        let mut waiter = tokio::task::spawn_blocking(move || wait_process(pid));
+        // 在独立的任务中写入标准输入，避免与读取输出互相阻塞在已满的管道上；
+        // 发送端关闭时管道随之关闭，程序读到 EOF；积压的数据受 `input_bytes` 限制
+        let (stdin_sender, mut stdin_receiver) = mpsc::unbounded_channel::<String>();
+        if let Some(pipe) = child.stdin.take() {
+            let mut pipe = ChildStdin::from_std(pipe)?;
+            tokio::spawn(async move {
+                while let Some(data) = stdin_receiver.recv().await {
+                    // 程序已关闭标准输入时忽略后续输入
+                    if pipe.write_all(data.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        let mut stdin_sender = Some(stdin_sender);
+        let mut commands_open = true;
+        let mut killed = false;
+
+        let (mut stdout, mut stderr) = match (child.stdout.take(), child.stderr.take()) {
+            (Some(stdout), Some(stderr)) => (
+                ChildStdout::from_std(stdout)?,
+                ChildStderr::from_std(stderr)?,
+            ),
+            _ => {
+                return Err(AppError::Server(
+                    "Failed to capture program output".to_string(),
+                ))
+            }
+        };
+        let mut out = StreamOutput::new(StdStream::Stdout);
+        let mut err = StreamOutput::new(StdStream::Stderr);
+        let mut budget = self.limits.output_bytes;
+        let mut input_budget = self.limits.input_bytes;
+        let (mut out_buf, mut err_buf) = ([0u8; 8192], [0u8; 8192]);
+        let (mut out_open, mut err_open) = (true, true);
+        let mut violation = None;
+        let deadline = sleep(Duration::from_secs(self.limits.wall_seconds));
+        tokio::pin!(deadline);
+
+        let mut exit_status = None;
+        while out_open || err_open || exit_status.is_none() {
+            tokio::select! {
+                read = stdout.read(&mut out_buf), if out_open => match read? {
+                    0 => out_open = false,
+                    n => if !out.push(&out_buf[..n], &mut budget, &mut on_line) {
+                        violation = Some(LimitViolation::Output);
+                        break;
+                    },
+                },
+                read = stderr.read(&mut err_buf), if err_open => match read? {
+                    0 => err_open = false,
+                    n => if !err.push(&err_buf[..n], &mut budget, &mut on_line) {
+                        violation = Some(LimitViolation::Output);
+                        break;
+                    },
+                },
+                command = commands.recv(), if commands_open => match command {
+                    Some(ProgramRunCommand::Input { data }) => {
+                        if let Some(sender) = &stdin_sender {
+                            if data.len() > input_budget {
+                                violation = Some(LimitViolation::Input);
+                                break;
+                            }
+                            input_budget -= data.len();
+                            on_line(StdStream::Stdin, data.clone());
+                            let _ = sender.send(data);
+                        }
+                    }
+                    Some(ProgramRunCommand::Eof) => stdin_sender = None,
+                    Some(ProgramRunCommand::Kill) => {
+                        killed = true;
+                        commands_open = false;
+                        sandbox.kill();
+                    }
+                    None => {
+                        stdin_sender = None;
+                        commands_open = false;
+                    }
+                },
+                exited = &mut waiter, if exit_status.is_none() => {
+                    exit_status = Some(join_wait(exited)?);
+                    // 结束程序遗留的后台进程，否则它们持有的管道不会关闭
+                    sandbox.exited();
+                }
+                _ = &mut deadline => {
+                    violation = Some(LimitViolation::WallTime);
+                    break;
+                }
+            }
+        }
+        if violation.is_some() {
+            sandbox.kill();
+        }
+        let (status, usage) = match exit_status {
+            Some(exited) => exited,
+            None => join_wait(waiter.await)?,
+        };
+        let stdout = out.finish(&mut on_line);
+        let stderr = err.finish(&mut on_line);
+
+        // 程序作为 PID 命名空间的 init 进程会忽略 SIGXCPU，直到硬限制时被内核 SIGKILL，
+        // 因此按 CPU 用量判断
+        let cpu_exhausted =
+            usage.cpu_user_ms + usage.cpu_system_ms >= self.limits.cpu_seconds as i64 * 1000;
+        let violation = violation.or_else(|| {
+            if status.signal() == Some(libc::SIGXCPU)
+                || (status.signal() == Some(libc::SIGKILL) && !killed && cpu_exhausted)
+            {
+                Some(LimitViolation::CpuTime)
+            } else if !status.success()
+                && stderr
+                    .lines()
+                    .last()
+                    .is_some_and(|line| line.starts_with("MemoryError"))
+            {
+                Some(LimitViolation::Memory)
+            } else {
+                None
+            }
+        });
+
+        Ok(PythonOutput {
+            exit_code: status.code(),
+            stdout,
+            stderr,
+            duration_ms: started.elapsed().as_millis() as i64,
+            violation,
+            network_isolated: isolated && self.limits.deny_network,
+            killed,
+            usage,
         })
     }
 
-    pub async fn execute_with_updates(
+    /// `mounts` 为空时不进入命名空间，直接在临时目录的 `work/` 中运行
+    fn command(
         &self,
-        _code: &str,
-        _updates: impl Fn(i32, Option<String>, Option<String>),
-    ) -> Result<(), AppError> {
+        scratch: &ScratchDir,
+        mounts: Option<SandboxMounts>,
+        switch_user: bool,
+    ) -> Command {
+        let workdir = match mounts {
+            Some(_) => PathBuf::from(SANDBOX_WORKDIR),
+            None => scratch.work(),
+        };
+        let mut command = Command::new(PYTHON_BIN);
+        command
+            .arg("-I") // 隔离模式: 忽略 PYTHON* 环境变量和用户 site-packages
+            .arg(workdir.join("main.py"))
+            .env_clear()
+            .env("PATH", SANDBOX_PATH)
+            .env("HOME", &workdir)
+            .env("TMPDIR", &workdir)
+            .env("LANG", "C.UTF-8")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if mounts.is_none() {
+            command.current_dir(&workdir);
+        }
+
+        let limits = self.limits;
+        // pre_exec 在 fork 之后、exec 之前执行，只能调用异步信号安全的系统调用，路径均已在父进程中准备好
+        unsafe {
+            command.pre_exec(move || restrict_process(&limits, mounts.as_ref(), switch_user));
+        }
+        command
+    }
+}
+
+/// 等待进程退出并回收，同时取得其资源使用情况
+fn wait_process(pid: i32) -> io::Result<(ExitStatus, ResourceUsage)> {
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    while unsafe { libc::wait4(pid, &mut status, 0, &mut usage) } < 0 {
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+
+    // tv_usec 在 macOS 上为 i32
+    #[allow(clippy::unnecessary_cast)]
+    let millis = |time: libc::timeval| time.tv_sec * 1000 + time.tv_usec as i64 / 1000;
+    Ok((
+        ExitStatus::from_raw(status),
+        ResourceUsage {
+            cpu_user_ms: millis(usage.ru_utime),
+            cpu_system_ms: millis(usage.ru_stime),
+            max_rss_kb: usage.ru_maxrss as i64,
+        },
+    ))
+}
+
+fn join_wait(
+    joined: Result<io::Result<(ExitStatus, ResourceUsage)>, tokio::task::JoinError>,
+) -> Result<(ExitStatus, ResourceUsage), AppError> {
+    joined
+        .map_err(|e| AppError::Server(e.to_string()))?
+        .map_err(AppError::from)
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
         Ok(())
     }
 }
+
+/// 在子进程中依次: 设置资源限制、创建新的会话；有 `mounts` 时进入命名空间（见
+/// `SandboxMounts::isolate`）；`switch_user` 时切换到运行用户。任一步失败都会使启动失败
+fn restrict_process(
+    limits: &SandboxLimits,
+    mounts: Option<&SandboxMounts>,
+    switch_user: bool,
+) -> io::Result<()> {
+    let rlimit = |soft: u64, hard: u64| libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+
+    unsafe {
+        // 超过软限制时收到 SIGXCPU（命名空间的 init 进程会忽略），硬限制多留一秒后由内核 SIGKILL
+        check(libc::setrlimit(
+            libc::RLIMIT_CPU,
+            &rlimit(limits.cpu_seconds, limits.cpu_seconds + 1),
+        ))?;
+        check(libc::setrlimit(
+            libc::RLIMIT_AS,
+            &rlimit(limits.memory_bytes, limits.memory_bytes),
+        ))?;
+        check(libc::setrlimit(
+            libc::RLIMIT_FSIZE,
+            &rlimit(limits.memory_bytes, limits.memory_bytes),
+        ))?;
+        check(libc::setrlimit(libc::RLIMIT_CORE, &rlimit(0, 0)))?;
+        // 按真实用户计数，因此只在切换到专用用户时设置，否则会与服务自身的线程一起计算
+        if switch_user {
+            check(libc::setrlimit(
+                libc::RLIMIT_NPROC,
+                &rlimit(limits.max_processes, limits.max_processes),
+            ))?;
+        }
+        check(libc::setsid())?;
+
+        if let Some(mounts) = mounts {
+            mounts.isolate(limits)?;
+        }
+
+        if switch_user {
+            check(libc::setgroups(0, std::ptr::null()))?;
+            check(libc::setgid(limits.gid))?;
+            check(libc::setuid(limits.uid))?;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            // 切换用户会清除父进程退出信号，因此在之后设置：外层进程被结束时 init 进程随之结束
+            if mounts.is_some() {
+                check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0))?;
+            }
+            // 禁止通过 setuid 程序重新获得权限
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+        }
+    }
+    Ok(())
+}
+
+/// 能否创建沙箱所需的命名空间，首次调用时探测并缓存结果；不可用时记录警告
+fn namespaces_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match probe_namespaces() {
+        Ok(()) => true,
+        Err(e) => {
+            warn!(
+                error = %e,
+                "Namespaces are unavailable, programs run with resource limits only"
+            );
+            false
+        }
+    })
+}
+
+/// 在子进程中尝试创建命名空间，子进程在 exec 之前退出
+#[cfg(target_os = "linux")]
+fn probe_namespaces() -> io::Result<()> {
+    let mut command = Command::new(PYTHON_BIN);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    unsafe {
+        command.pre_exec(|| {
+            check(libc::unshare(SANDBOX_NAMESPACES | libc::CLONE_NEWNET))?;
+            libc::_exit(0)
+        });
+    }
+    command.spawn()?.wait()?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn probe_namespaces() -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "namespaces are only supported on Linux",
+    ))
+}
+
+/// 命名空间的 init 进程 ID，供外层进程的 SIGTERM 处理函数使用
+#[cfg(target_os = "linux")]
+static SANDBOX_INIT: AtomicI32 = AtomicI32::new(0);
+
+/// 外层进程收到 SIGTERM 时结束 init 进程，命名空间内的其余进程由内核随之结束
+#[cfg(target_os = "linux")]
+extern "C" fn kill_init(_: libc::c_int) {
+    let init = SANDBOX_INIT.load(Ordering::SeqCst);
+    if init > 0 {
+        unsafe {
+            libc::kill(init, libc::SIGKILL);
+        }
+    }
+}
+
+/// 命名空间外的进程: 关闭所有文件描述符（包括标准库用于报告 exec 失败的管道，
+/// 否则启动会一直等到程序结束），收到 SIGTERM 时结束 init 进程，
+/// 等待 init 进程退出后以相同的退出码或信号退出
+///
+/// 服务等待和结束的是这个进程，由它回收 init 进程，资源使用情况因此包含程序本身。
+#[cfg(target_os = "linux")]
+unsafe fn supervise_init(init: libc::pid_t, terminate: &libc::sigset_t) -> ! {
+    SANDBOX_INIT.store(init, Ordering::SeqCst);
+    libc::signal(libc::SIGTERM, kill_init as *const () as libc::sighandler_t);
+    libc::sigprocmask(libc::SIG_UNBLOCK, terminate, std::ptr::null_mut());
+
+    if libc::syscall(libc::SYS_close_range, 0, libc::c_uint::MAX, 0) < 0 {
+        let mut limit: libc::rlimit = std::mem::zeroed();
+        libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit);
+        for fd in 0..limit.rlim_cur.min(65536) as libc::c_int {
+            libc::close(fd);
+        }
+    }
+
+    let mut status = 0;
+    while libc::waitpid(init, &mut status, 0) < 0 {
+        if *libc::__errno_location() != libc::EINTR {
+            libc::_exit(1);
+        }
+    }
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        libc::signal(signal, libc::SIG_DFL);
+        libc::kill(libc::getpid(), signal);
+        libc::_exit(128 + signal);
+    }
+    libc::_exit(libc::WEXITSTATUS(status))
+}
+
+fn check_syntax(code: &str) -> Result<String, ProgramCompileResponse> {
+    Python::with_gil(|py| {
+        let result = (|| -> PyResult<String> {
+            py.import("builtins")?
+                .getattr("compile")?
+                .call1((code, SCRIPT_NAME, "exec"))?;
+            let ast = py.import("ast")?;
+            if !ast.hasattr("unparse")? {
+                return Ok(code.to_string());
+            }
+            let tree = ast.getattr("parse")?.call1((code, SCRIPT_NAME))?;
+            ast.getattr("unparse")?.call1((tree,))?.extract()
+        })();
+        result.map_err(|err| syntax_error(py, &err))
+    })
+}
+
+/// 从 Python 异常中提取错误位置；非 `SyntaxError`（如源码包含空字节）只保留类型和信息
+fn syntax_error(py: Python<'_>, err: &PyErr) -> ProgramCompileResponse {
+    let value = err.value(py);
+    let error_type = err.get_type(py).name().ok().map(str::to_string);
+    if !err.is_instance_of::<PySyntaxError>(py) {
+        return compile_error(error_type, None, Some(value.to_string()), None);
+    }
+
+    let attr = |name: &str| value.getattr(name).ok().filter(|v| !v.is_none());
+    compile_error(
+        error_type,
+        attr("filename").and_then(|v| v.extract().ok()),
+        attr("msg").and_then(|v| v.extract().ok()),
+        attr("lineno").and_then(|v| v.extract().ok()),
+    )
+}
+
+fn compile_error(
+    error_type: Option<String>,
+    error_file: Option<String>,
+    error_message: Option<String>,
+    error_line: Option<i32>,
+) -> ProgramCompileResponse {
+    ProgramCompileResponse {
+        status: ProgramStatus::Failed,
+        time: Utc::now(),
+        error_file,
+        error_type,
+        error_line,
+        error_message,
+        error_suggestions: None,
+    }
+}
+
+/// 程序的标准输入输出流
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StdStream {
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+/// 收集一个输出流，并按行切分
+struct StreamOutput {
+    stream: StdStream,
+    collected: Vec<u8>,
+    line_start: usize, // 尚未回调的行在 `collected` 中的起始位置
+}
+
+impl StreamOutput {
+    fn new(stream: StdStream) -> Self {
+        Self {
+            stream,
+            collected: Vec::new(),
+            line_start: 0,
+        }
+    }
+
+    /// 追加读取到的数据并回调已完整的行；超出剩余的输出额度时只保留额度内的部分并返回 false
+    fn push(
+        &mut self,
+        bytes: &[u8],
+        budget: &mut usize,
+        on_line: &mut impl FnMut(StdStream, String),
+    ) -> bool {
+        let accepted = bytes.len().min(*budget);
+        *budget -= accepted;
+        self.collected.extend_from_slice(&bytes[..accepted]);
+
+        while let Some(end) = self.collected[self.line_start..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+        {
+            let end = self.line_start + end;
+            let line = String::from_utf8_lossy(&self.collected[self.line_start..end]);
+            on_line(self.stream, line.trim_end_matches('\r').to_string());
+            self.line_start = end + 1;
+        }
+        accepted == bytes.len()
+    }
+
+    /// 回调最后一行不以换行结尾的输出，返回完整输出
+    fn finish(self, on_line: &mut impl FnMut(StdStream, String)) -> String {
+        if self.line_start < self.collected.len() {
+            let line = String::from_utf8_lossy(&self.collected[self.line_start..]);
+            on_line(self.stream, line.into_owned());
+        }
+        String::from_utf8_lossy(&self.collected).into_owned()
+    }
+}
+
+/// 程序的临时目录，离开作用域时删除
+///
+/// `root/` 是沙箱根目录的挂载点，`work/` 属于运行用户，挂载为沙箱内的 `/work`。
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    async fn create() -> Result<Self, AppError> {
+        let path = std::env::temp_dir().join(format!("program-{}", Uuid::new_v4()));
+        tokio::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&path)
+            .await?;
+        Ok(Self(path))
+    }
+
+    fn work(&self) -> PathBuf {
+        self.0.join("work")
+    }
+
+    /// 写入程序，`isolated` 时准备挂载计划
+    async fn prepare(
+        &self,
+        code: &str,
+        limits: &SandboxLimits,
+        isolated: bool,
+        switch_user: bool,
+    ) -> io::Result<Option<SandboxMounts>> {
+        let root = self.0.join("root");
+        let work = self.work();
+        tokio::fs::create_dir(&root).await?;
+        tokio::fs::create_dir(&work).await?;
+        let script = work.join("main.py");
+        tokio::fs::write(&script, code).await?;
+        if switch_user {
+            chown(&work, Some(limits.uid), Some(limits.gid))?;
+            chown(&script, Some(limits.uid), Some(limits.gid))?;
+        }
+        if isolated {
+            SandboxMounts::new(&root, &work).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// 沙箱根目录中的一项
+#[cfg(target_os = "linux")]
+enum MountEntry {
+    /// 绑定挂载目录或文件，挂载后以 `flags` 重新挂载（如只读）
+    Bind {
+        source: CString,
+        target: CString,
+        directory: bool,
+        flags: libc::c_ulong,
+    },
+    /// 重建系统目录的符号链接（如 `/lib -> usr/lib`）
+    Symlink { link: CString, path: CString },
+    /// 创建空目录
+    Directory(CString),
+}
+
+/// 沙箱根目录的挂载计划
+///
+/// 路径在父进程中转换为 `CString`，子进程在 `pre_exec` 中只执行系统调用。
+#[cfg(target_os = "linux")]
+struct SandboxMounts {
+    root: CString,
+    workdir: CString,
+    entries: Vec<MountEntry>,
+}
+
+/// 其他平台没有命名空间，不会创建挂载计划
+#[cfg(not(target_os = "linux"))]
+enum SandboxMounts {}
+
+#[cfg(not(target_os = "linux"))]
+impl SandboxMounts {
+    fn new(_root: &Path, _work: &Path) -> io::Result<Self> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    unsafe fn isolate(&self, _limits: &SandboxLimits) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SandboxMounts {
+    fn new(root: &Path, work: &Path) -> io::Result<Self> {
+        let target = |path: &str| c_path(&root.join(path.trim_start_matches('/')));
+        let read_only = libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV;
+
+        let mut entries = Vec::new();
+        for path in SANDBOX_SYSTEM_PATHS {
+            let Ok(metadata) = std::fs::symlink_metadata(path) else {
+                continue;
+            };
+            if metadata.file_type().is_symlink() {
+                entries.push(MountEntry::Symlink {
+                    link: c_path(&std::fs::read_link(path)?)?,
+                    path: target(path)?,
+                });
+            } else {
+                entries.push(MountEntry::Bind {
+                    source: c_path(Path::new(path))?,
+                    target: target(path)?,
+                    directory: true,
+                    flags: read_only,
+                });
+            }
+        }
+        entries.push(MountEntry::Bind {
+            source: c_path(work)?,
+            target: target(SANDBOX_WORKDIR)?,
+            directory: true,
+            flags: libc::MS_NOSUID | libc::MS_NODEV,
+        });
+        entries.push(MountEntry::Directory(target("/dev")?));
+        for device in SANDBOX_DEVICES {
+            if !Path::new(device).exists() {
+                continue;
+            }
+            // 设备文件所在的挂载不能带 nodev
+            entries.push(MountEntry::Bind {
+                source: c_path(Path::new(device))?,
+                target: target(device)?,
+                directory: false,
+                flags: libc::MS_NOSUID,
+            });
+        }
+
+        Ok(Self {
+            root: c_path(root)?,
+            workdir: CString::new(SANDBOX_WORKDIR)?,
+            entries,
+        })
+    }
+
+    /// 创建新的 PID、挂载（和网络）命名空间，再 fork 出命名空间的 init 进程；init 进程
+    /// 构建根目录后返回，继续切换用户并 exec 程序；原进程留在命名空间外等待它退出并以相同的
+    /// 状态退出，见 `supervise_init`
+    unsafe fn isolate(&self, limits: &SandboxLimits) -> io::Result<()> {
+        let mut namespaces = SANDBOX_NAMESPACES;
+        if limits.deny_network {
+            namespaces |= libc::CLONE_NEWNET;
+        }
+        check(libc::unshare(namespaces))?;
+        // 记录 init 进程 ID 之前收到的 SIGTERM 暂缓处理
+        let mut terminate: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut terminate);
+        libc::sigaddset(&mut terminate, libc::SIGTERM);
+        check(libc::sigprocmask(
+            libc::SIG_BLOCK,
+            &terminate,
+            std::ptr::null_mut(),
+        ))?;
+        // unshare 之后创建的第一个子进程才进入新的 PID 命名空间
+        let init = libc::fork();
+        check(init)?;
+        if init > 0 {
+            supervise_init(init, &terminate);
+        }
+        // 屏蔽的信号集会保留到 exec 之后
+        check(libc::sigprocmask(
+            libc::SIG_UNBLOCK,
+            &terminate,
+            std::ptr::null_mut(),
+        ))?;
+        self.enter()
+    }
+
+    /// 在新的挂载命名空间中构建根目录，chroot 后切换到工作目录
+    fn enter(&self) -> io::Result<()> {
+        let null = std::ptr::null();
+        unsafe {
+            // 之后的挂载不传播回宿主
+            check(libc::mount(
+                null,
+                c"/".as_ptr(),
+                null,
+                libc::MS_REC | libc::MS_PRIVATE,
+                null.cast(),
+            ))?;
+            check(libc::mount(
+                c"tmpfs".as_ptr(),
+                self.root.as_ptr(),
+                c"tmpfs".as_ptr(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                c"size=1m,mode=0755".as_ptr().cast(),
+            ))?;
+            for entry in &self.entries {
+                match entry {
+                    MountEntry::Directory(path) => check(libc::mkdir(path.as_ptr(), 0o755))?,
+                    MountEntry::Symlink { link, path } => {
+                        check(libc::symlink(link.as_ptr(), path.as_ptr()))?
+                    }
+                    MountEntry::Bind {
+                        source,
+                        target,
+                        directory,
+                        flags,
+                    } => {
+                        if *directory {
+                            check(libc::mkdir(target.as_ptr(), 0o755))?;
+                        } else {
+                            let fd = libc::open(
+                                target.as_ptr(),
+                                libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC,
+                                0o644,
+                            );
+                            check(fd)?;
+                            libc::close(fd);
+                        }
+                        check(libc::mount(
+                            source.as_ptr(),
+                            target.as_ptr(),
+                            null,
+                            libc::MS_BIND | libc::MS_REC,
+                            null.cast(),
+                        ))?;
+                        check(libc::mount(
+                            null,
+                            target.as_ptr(),
+                            null,
+                            libc::MS_BIND | libc::MS_REMOUNT | flags,
+                            null.cast(),
+                        ))?;
+                    }
+                }
+            }
+            check(libc::chroot(self.root.as_ptr()))?;
+            check(libc::chdir(self.workdir.as_ptr()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn c_path(path: &Path) -> io::Result<CString> {
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 沙箱的外层进程，离开作用域时结束沙箱内仍在运行的进程（包括程序创建的子进程）
+///
+/// 在命名空间中运行时，外层进程收到 SIGTERM 后结束 PID 命名空间的 init 进程并将其回收，
+/// 见 `supervise_init`；否则结束程序的进程组。
+struct SandboxProcess {
+    pid: i32,
+    isolated: bool, // 在 PID 命名空间中运行
+    exited: bool,   // 已被回收，进程 ID 可能已被复用
+}
+
+impl SandboxProcess {
+    fn new(pid: i32, isolated: bool) -> Self {
+        Self {
+            pid,
+            isolated,
+            exited: false,
+        }
+    }
+
+    /// 程序已被回收；不在命名空间中时结束进程组中遗留的后台进程
+    fn exited(&mut self) {
+        self.exited = true;
+        self.kill();
+    }
+
+    fn kill(&self) {
+        unsafe {
+            if !self.isolated {
+                // 进程组 ID 在组内仍有进程时不会被复用
+                libc::kill(-self.pid, libc::SIGKILL);
+            } else if !self.exited {
+                libc::kill(self.pid, libc::SIGTERM);
+            }
+        }
+    }
+}
+
+impl Drop for SandboxProcess {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_syntax_normalizes_source() {
+        let normalized = check_syntax("x=1\nprint( x )\n").unwrap();
+        assert_eq!(normalized.trim_end(), "x = 1\nprint(x)");
+    }
+
+    #[test]
+    fn check_syntax_reports_error_location() {
+        let error = check_syntax("x = 1\nif x\n    pass\n").unwrap_err();
+        assert_eq!(error.status, ProgramStatus::Failed);
+        assert_eq!(error.error_type.as_deref(), Some("SyntaxError"));
+        assert_eq!(error.error_file.as_deref(), Some(SCRIPT_NAME));
+        assert_eq!(error.error_line, Some(2));
+    }
+
+    /// 依次推入 `chunks`，返回回调的行和完整输出
+    fn split_lines(chunks: &[&str], mut budget: usize) -> (Vec<String>, String, bool) {
+        let mut lines = Vec::new();
+        let mut on_line = |stream: StdStream, line: String| {
+            assert_eq!(stream, StdStream::Stdout);
+            lines.push(line);
+        };
+        let mut output = StreamOutput::new(StdStream::Stdout);
+        let within_budget = chunks
+            .iter()
+            .all(|chunk| output.push(chunk.as_bytes(), &mut budget, &mut on_line));
+        let collected = output.finish(&mut on_line);
+        (lines, collected, within_budget)
+    }
+
+    #[test]
+    fn stream_output_joins_partial_chunks_into_lines() {
+        let (lines, collected, within_budget) = split_lines(&["ab", "c\nd", "e\r\nf\n"], 1024);
+        assert_eq!(lines, ["abc", "de", "f"]);
+        assert_eq!(collected, "abc\nde\r\nf\n");
+        assert!(within_budget);
+    }
+
+    #[test]
+    fn stream_output_flushes_trailing_line_without_newline() {
+        let (lines, collected, _) = split_lines(&["first\nlast"], 1024);
+        assert_eq!(lines, ["first", "last"]);
+        assert_eq!(collected, "first\nlast");
+
+        let (lines, collected, _) = split_lines(&[], 1024);
+        assert!(lines.is_empty());
+        assert_eq!(collected, "");
+    }
+
+    #[test]
+    fn stream_output_keeps_only_the_budget() {
+        let (lines, collected, within_budget) = split_lines(&["one\n", "two\nthree\n"], 6);
+        assert_eq!(lines, ["one", "tw"]);
+        assert_eq!(collected, "one\ntw");
+        assert!(!within_budget);
+    }
+
+    fn executor(limits: SandboxLimits) -> PythonExecutor {
+        PythonExecutor::new().with_limits(limits)
+    }
+
+    #[tokio::test]
+    async fn run_passes_input_and_exit_code() {
+        let output = executor(SandboxLimits::default())
+            .run(
+                "import json, sys\nprint(json.load(sys.stdin)['name'])\nsys.exit(3)\n",
+                &serde_json::json!({ "name": "report" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, "report\n");
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.violation, None);
+        assert!(!output.success());
+    }
+
+    #[tokio::test]
+    async fn environment_is_scrubbed() {
+        let output = executor(SandboxLimits::default())
+            .run(
+                "import os\nprint(sorted(k for k in os.environ if k != 'LC_CTYPE'))\n",
+                &Value::Null,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            output.stdout.trim_end(),
+            "['HOME', 'LANG', 'PATH', 'TMPDIR']"
+        );
+    }
+
+    #[tokio::test]
+    async fn output_is_capped_at_the_budget() {
+        let limits = SandboxLimits {
+            output_bytes: 1000,
+            ..SandboxLimits::default()
+        };
+        let output = executor(limits)
+            .run("while True:\n    print('x' * 100)\n", &Value::Null)
+            .await
+            .unwrap();
+        assert_eq!(output.violation, Some(LimitViolation::Output));
+        assert_eq!(output.stdout.len() + output.stderr.len(), 1000);
+    }
+
+    #[tokio::test]
+    async fn wall_clock_limit_kills_the_program() {
+        let limits = SandboxLimits {
+            wall_seconds: 1,
+            ..SandboxLimits::default()
+        };
+        let started = Instant::now();
+        let output = executor(limits)
+            .run(
+                "import time\nprint('started', flush=True)\ntime.sleep(30)\n",
+                &Value::Null,
+            )
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(output.violation, Some(LimitViolation::WallTime));
+        assert_eq!(output.stdout, "started\n");
+        assert_eq!(output.exit_code, None);
+    }
+
+    #[tokio::test]
+    async fn cpu_limit_stops_busy_loops() {
+        let limits = SandboxLimits {
+            cpu_seconds: 1,
+            ..SandboxLimits::default()
+        };
+        let output = executor(limits)
+            .run("while True:\n    pass\n", &Value::Null)
+            .await
+            .unwrap();
+        assert_eq!(output.violation, Some(LimitViolation::CpuTime));
+        // 内核按时钟节拍计费，用量可能略低于限制
+        assert!(output.usage.cpu_user_ms + output.usage.cpu_system_ms >= 900);
+    }
+
+    #[tokio::test]
+    async fn input_frames_reach_the_program() {
+        let (sender, commands) = mpsc::channel(4);
+        sender
+            .send(ProgramRunCommand::Input {
+                data: "Ada\n".to_string(),
+            })
+            .await
+            .unwrap();
+        let mut lines = Vec::new();
+        let output = executor(SandboxLimits::default())
+            .execute_with_updates(
+                "name = input('name? ')\nprint('hello', name)\n",
+                commands,
+                |stream, line| lines.push((stream, line)),
+            )
+            .await
+            .unwrap();
+        drop(sender);
+        assert_eq!(output.exit_code, Some(0));
+        assert_eq!(output.stdout, "name? hello Ada\n");
+        assert_eq!(
+            lines,
+            [
+                (StdStream::Stdin, "Ada\n".to_string()),
+                (StdStream::Stdout, "name? hello Ada".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn eof_closes_stdin() {
+        let (sender, commands) = mpsc::channel(4);
+        sender
+            .send(ProgramRunCommand::Input {
+                data: "1\n2\n".to_string(),
+            })
+            .await
+            .unwrap();
+        sender.send(ProgramRunCommand::Eof).await.unwrap();
+        let output = executor(SandboxLimits::default())
+            .execute_with_updates(
+                "import sys\nprint(len(sys.stdin.read().split()))\n",
+                commands,
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+        // 发送端仍然打开，程序只能从 `Eof` 得知输入结束
+        drop(sender);
+        assert_eq!(output.stdout, "2\n");
+        assert_eq!(output.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn kill_command_ends_the_program() {
+        let (sender, commands) = mpsc::channel(4);
+        let started = Instant::now();
+        let output = executor(SandboxLimits::default())
+            .execute_with_updates(
+                "import time\nprint('started', flush=True)\ntime.sleep(30)\n",
+                commands,
+                |stream, _| {
+                    if stream == StdStream::Stdout {
+                        let _ = sender.try_send(ProgramRunCommand::Kill);
+                    }
+                },
+            )
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(output.killed);
+        assert_eq!(output.stdout, "started\n");
+        assert_eq!(output.violation, None);
+        assert_eq!(output.exit_code, None);
+    }
+
+    /// 程序的后台进程通过 `setsid()` 离开进程组后，仍随 PID 命名空间一起结束
+    #[tokio::test]
+    #[ignore = "requires root (CAP_SYS_ADMIN) for namespaces"]
+    async fn detached_children_end_with_the_program() {
+        assert!(namespaces_available());
+        let started = Instant::now();
+        let output = executor(SandboxLimits::default())
+            .run(
+                "import os, time\nif os.fork() == 0:\n    os.setsid()\n    time.sleep(30)\nelse:\n    print('done')\n",
+                &Value::Null,
+            )
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(output.stdout, "done\n");
+        assert_eq!(output.exit_code, Some(0));
+        assert!(output.network_isolated);
+    }
+
+    #[test]
+    fn check_syntax_rejects_null_bytes() {
+        let error = check_syntax("x = 1\0").unwrap_err();
+        assert!(error.error_type.is_some());
+        assert_eq!(error.error_line, None);
+    }
+}